@@ -1,7 +1,12 @@
-use crate::fun::{display::DisplayFn, Name};
+use crate::fun::{
+  display::DisplayFn,
+  parser::{ParseError, ParseErrorKind},
+  Name, Span,
+};
 use std::{
   collections::BTreeMap,
   fmt::{Display, Formatter},
+  path::PathBuf,
 };
 
 pub const ERR_INDENT_SIZE: usize = 2;
@@ -22,16 +27,35 @@ pub struct DiagnosticsConfig {
   pub unused_definition: Severity,
   pub repeated_bind: Severity,
   pub recursion_cycle: Severity,
+  pub never_evaluated: Severity,
+  pub partial_application: Severity,
+  pub float_equality: Severity,
+  pub shadowed_variable: Severity,
+  /// Decides the severity of each warning as it's emitted, instead of the ones above.
+  ///
+  /// Receives where the warning comes from, its type and the severity configured above,
+  /// allowing policies like only denying unused definitions in library code.
+  /// Warnings decided as [Severity::Error] stop the compilation like any other error.
+  pub warning_policy: Option<WarningPolicy>,
 }
 
+pub type WarningPolicy = fn(&DiagnosticOrigin, WarningType, Severity) -> Severity;
+
+/// Some of the warnings of a [Diagnostics], with where they come from.
+pub type WarningList<'a> = Vec<(&'a DiagnosticOrigin, &'a Diagnostic)>;
+
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
   message: String,
   severity: Severity,
+  warning_type: Option<WarningType>,
+  parse_error: Option<(ParseErrorKind, Span)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DiagnosticOrigin {
+  /// A syntax error in a source file.
+  Parsing(PathBuf),
   /// An error from the relationship between multiple top-level definitions.
   Book,
   /// An error in a pattern-matching function definition rule.
@@ -49,7 +73,7 @@ pub enum Severity {
   Error,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum WarningType {
   IrrefutableMatch,
   RedundantMatch,
@@ -57,6 +81,10 @@ pub enum WarningType {
   UnusedDefinition,
   RepeatedBind,
   RecursionCycle,
+  NeverEvaluated,
+  PartialApplication,
+  FloatEquality,
+  ShadowedVariable,
 }
 
 impl Diagnostics {
@@ -80,26 +108,53 @@ impl Diagnostics {
   }
 
   pub fn add_rule_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType, def_name: Name) {
-    let severity = self.config.warning_severity(warn_type);
-    if severity == Severity::Error {
-      self.err_counter += 1;
-    }
-    self.add_diagnostic(warn, severity, DiagnosticOrigin::Rule(def_name.def_name_from_generated()));
+    self.add_warning(warn, warn_type, DiagnosticOrigin::Rule(def_name.def_name_from_generated()));
   }
 
   pub fn add_book_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType) {
-    let severity = self.config.warning_severity(warn_type);
+    self.add_warning(warn, warn_type, DiagnosticOrigin::Book);
+  }
+
+  fn add_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType, orig: DiagnosticOrigin) {
+    let mut severity = self.config.warning_severity(warn_type);
+    if let Some(policy) = self.config.warning_policy {
+      severity = policy(&orig, warn_type, severity);
+    }
     if severity == Severity::Error {
       self.err_counter += 1;
     }
-    self.add_diagnostic(warn, severity, DiagnosticOrigin::Book);
+    self.add_typed_diagnostic(warn, severity, Some(warn_type), orig);
   }
 
   pub fn add_diagnostic(&mut self, msg: impl ToString, severity: Severity, orig: DiagnosticOrigin) {
-    let diag = Diagnostic { message: msg.to_string(), severity };
+    self.add_typed_diagnostic(msg, severity, None, orig)
+  }
+
+  fn add_typed_diagnostic(
+    &mut self,
+    msg: impl ToString,
+    severity: Severity,
+    warning_type: Option<WarningType>,
+    orig: DiagnosticOrigin,
+  ) {
+    let diag = Diagnostic { message: msg.to_string(), severity, warning_type, parse_error: None };
     self.diagnostics.entry(orig).or_default().push(diag)
   }
 
+  /// Adds a syntax error found while parsing the file at `path`, keeping its kind and location.
+  pub fn add_parse_error(&mut self, err: ParseError, path: impl Into<PathBuf>) {
+    self.err_counter += 1;
+    let ParseError { kind, msg, span, line, column } = err;
+    let span = Span { start: span.start, end: span.end, line, column };
+    let diag = Diagnostic {
+      message: msg,
+      severity: Severity::Error,
+      warning_type: None,
+      parse_error: Some((kind, span)),
+    };
+    self.diagnostics.entry(DiagnosticOrigin::Parsing(path.into())).or_default().push(diag)
+  }
+
   pub fn take_rule_err<T, E: std::fmt::Display>(
     &mut self,
     result: Result<T, E>,
@@ -148,6 +203,25 @@ impl Diagnostics {
     if self.err_counter == 0 { Ok(t) } else { Err(std::mem::take(self)) }
   }
 
+  /// Splits the warnings into the ones to show as warnings and the ones denied as errors,
+  /// without the allowed ones, in the order they're displayed.
+  ///
+  /// This lets callers present the warnings however they want, instead of with the Display of [Diagnostics].
+  pub fn partition_warnings(&self) -> (WarningList<'_>, WarningList<'_>) {
+    let mut warned = vec![];
+    let mut denied = vec![];
+    for (orig, diags) in &self.diagnostics {
+      for diag in diags {
+        match (diag.severity, diag.warning_type) {
+          (Severity::Warning, _) => warned.push((orig, diag)),
+          (Severity::Error, Some(_)) => denied.push((orig, diag)),
+          _ => {}
+        }
+      }
+    }
+    (warned, denied)
+  }
+
   /// Returns a Display that prints the diagnostics with one of the given severities.
   pub fn display_with_severity(&self, severity: Severity) -> impl std::fmt::Display + '_ {
     DisplayFn(move |f| {
@@ -163,6 +237,12 @@ impl Diagnostics {
         let mut errs = filter(errs, severity).peekable();
         if errs.peek().is_some() {
           match orig {
+            DiagnosticOrigin::Parsing(path) => {
+              writeln!(f, "In {} :", path.display())?;
+              for err in errs {
+                writeln!(f, "{err}")?;
+              }
+            }
             DiagnosticOrigin::Book => {
               for err in errs {
                 writeln!(f, "{err}")?;
@@ -196,6 +276,47 @@ impl Diagnostics {
       Ok(())
     })
   }
+
+  /// Returns a Display that prints a one-line summary of the warnings,
+  /// counting how many were emitted of each kind instead of showing them.
+  /// Warnings allowed by the [DiagnosticsConfig] aren't counted, and the denied ones are shown with the errors.
+  ///
+  /// Example: `Warnings: 3 unused-definition, 1 repeated-bind`
+  pub fn display_warnings_summary(&self) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      let mut counts = BTreeMap::<Option<WarningType>, usize>::new();
+      for (_, diag) in self.partition_warnings().0 {
+        *counts.entry(diag.warning_type).or_default() += 1;
+      }
+      if counts.is_empty() {
+        return Ok(());
+      }
+
+      let counts = counts
+        .into_iter()
+        .map(|(warn, count)| match warn {
+          Some(warn) => format!("{count} {warn}"),
+          None => format!("{count} other"),
+        })
+        .collect::<Vec<_>>();
+      writeln!(f, "\x1b[4m\x1b[1m\x1b[33mWarnings:\x1b[0m {}", counts.join(", "))
+    })
+  }
+
+  /// Like the Display of [Diagnostics], but with the warnings replaced by their [Self::display_warnings_summary].
+  pub fn display_summarized(&self) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      write!(f, "{}", self.display_warnings_summary())?;
+      self.fmt_errors(f)
+    })
+  }
+
+  fn fmt_errors(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    if self.has_severity(Severity::Error) {
+      write!(f, "\x1b[4m\x1b[1m\x1b[31mErrors:\x1b[0m\n{}", self.display_with_severity(Severity::Error))?;
+    }
+    Ok(())
+  }
 }
 
 impl Display for Diagnostics {
@@ -203,10 +324,7 @@ impl Display for Diagnostics {
     if self.has_severity(Severity::Warning) {
       write!(f, "\x1b[4m\x1b[1m\x1b[33mWarnings:\x1b[0m\n{}", self.display_with_severity(Severity::Warning))?;
     }
-    if self.has_severity(Severity::Error) {
-      write!(f, "\x1b[4m\x1b[1m\x1b[31mErrors:\x1b[0m\n{}", self.display_with_severity(Severity::Error))?;
-    }
-    Ok(())
+    self.fmt_errors(f)
   }
 }
 
@@ -216,13 +334,42 @@ impl From<String> for Diagnostics {
       diagnostics: BTreeMap::from_iter([(DiagnosticOrigin::Book, vec![Diagnostic {
         message: value,
         severity: Severity::Error,
+        warning_type: None,
+        parse_error: None,
       }])]),
       ..Default::default()
     }
   }
 }
 
+impl Diagnostic {
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+
+  pub fn severity(&self) -> Severity {
+    self.severity
+  }
+
+  /// The kind of warning, or `None` if this is not a warning.
+  pub fn warning_type(&self) -> Option<WarningType> {
+    self.warning_type
+  }
+
+  /// The kind of syntax error, or `None` if this is not a syntax error.
+  pub fn parse_error_kind(&self) -> Option<ParseErrorKind> {
+    self.parse_error.map(|(kind, _)| kind)
+  }
+
+  /// Where in the source file the syntax error was found, or `None` if this is not a syntax error.
+  pub fn span(&self) -> Option<Span> {
+    self.parse_error.map(|(_, span)| span)
+  }
+}
+
 impl DiagnosticsConfig {
+  /// Sets all the warnings to `severity`, except for the opt-in `partial_application` and `shadowed_variable`,
+  /// which are always allowed.
   pub fn new(severity: Severity, verbose: bool) -> Self {
     Self {
       irrefutable_match: severity,
@@ -231,6 +378,11 @@ impl DiagnosticsConfig {
       unused_definition: severity,
       repeated_bind: severity,
       recursion_cycle: severity,
+      never_evaluated: severity,
+      partial_application: Severity::Allow,
+      float_equality: severity,
+      shadowed_variable: Severity::Allow,
+      warning_policy: None,
       verbose,
     }
   }
@@ -243,6 +395,10 @@ impl DiagnosticsConfig {
       WarningType::IrrefutableMatch => self.irrefutable_match,
       WarningType::RedundantMatch => self.redundant_match,
       WarningType::UnreachableMatch => self.unreachable_match,
+      WarningType::NeverEvaluated => self.never_evaluated,
+      WarningType::PartialApplication => self.partial_application,
+      WarningType::FloatEquality => self.float_equality,
+      WarningType::ShadowedVariable => self.shadowed_variable,
     }
   }
 }
@@ -255,8 +411,103 @@ impl Default for DiagnosticsConfig {
   }
 }
 
+impl Display for WarningType {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      WarningType::IrrefutableMatch => write!(f, "irrefutable-match"),
+      WarningType::RedundantMatch => write!(f, "redundant-match"),
+      WarningType::UnreachableMatch => write!(f, "unreachable-match"),
+      WarningType::UnusedDefinition => write!(f, "unused-definition"),
+      WarningType::RepeatedBind => write!(f, "repeated-bind"),
+      WarningType::RecursionCycle => write!(f, "recursion-cycle"),
+      WarningType::NeverEvaluated => write!(f, "never-evaluated"),
+      WarningType::PartialApplication => write!(f, "partial-application"),
+      WarningType::FloatEquality => write!(f, "float-equality"),
+      WarningType::ShadowedVariable => write!(f, "shadowed-variable"),
+    }
+  }
+}
+
 impl Display for Diagnostic {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     write!(f, "{}", self.message)
   }
 }
+
+#[test]
+fn warning_policy() {
+  let mut diags = Diagnostics::new(DiagnosticsConfig {
+    warning_policy: Some(|orig, _, severity| match orig {
+      DiagnosticOrigin::Rule(nam) if nam.starts_with("Lib/") => Severity::Error,
+      _ => severity,
+    }),
+    ..DiagnosticsConfig::new(Severity::Warning, false)
+  });
+  diags.start_pass();
+  diags.add_rule_warning("Definition is unused.", WarningType::UnusedDefinition, Name::new("script"));
+  assert!(diags.fatal(()).is_ok());
+  diags.add_rule_warning("Definition is unused.", WarningType::UnusedDefinition, Name::new("Lib/foo"));
+  let diags = diags.fatal(()).unwrap_err();
+
+  let severities = |nam: &str| {
+    diags.diagnostics[&DiagnosticOrigin::Rule(Name::new(nam))]
+      .iter()
+      .map(Diagnostic::severity)
+      .collect::<Vec<_>>()
+  };
+  assert_eq!(severities("Lib/foo"), [Severity::Error]);
+  assert_eq!(severities("script"), [Severity::Warning]);
+}
+
+#[test]
+fn partition_warnings() {
+  let mut diags = Diagnostics::new(DiagnosticsConfig {
+    unused_definition: Severity::Error,
+    repeated_bind: Severity::Allow,
+    ..DiagnosticsConfig::new(Severity::Warning, false)
+  });
+  diags.add_rule_warning("Definition is unused.", WarningType::UnusedDefinition, Name::new("foo"));
+  diags.add_rule_warning("Repeated bind 'x'.", WarningType::RepeatedBind, Name::new("foo"));
+  diags.add_rule_warning("Irrefutable match.", WarningType::IrrefutableMatch, Name::new("foo"));
+  diags.add_book_warning("Recursion cycle.", WarningType::RecursionCycle);
+  diags.add_rule_error("Unbound variable 'x'.", Name::new("foo"));
+
+  fn messages(diags: WarningList) -> Vec<&str> {
+    diags.into_iter().map(|(_, diag)| diag.message()).collect()
+  }
+  let (warned, denied) = diags.partition_warnings();
+  assert_eq!(messages(warned), ["Recursion cycle.", "Irrefutable match."]);
+  assert_eq!(messages(denied), ["Definition is unused."]);
+}
+
+#[test]
+fn warnings_summary() {
+  let mut diags = Diagnostics::new(DiagnosticsConfig {
+    unused_definition: Severity::Warning,
+    repeated_bind: Severity::Allow,
+    ..DiagnosticsConfig::new(Severity::Allow, false)
+  });
+  diags.add_rule_warning("Definition is unused.", WarningType::UnusedDefinition, Name::new("foo"));
+  diags.add_rule_warning("Definition is unused.", WarningType::UnusedDefinition, Name::new("baz"));
+  diags.add_rule_warning("Repeated bind 'x'.", WarningType::RepeatedBind, Name::new("foo"));
+  diags.add_book_warning("Recursion cycle.", WarningType::RecursionCycle);
+
+  let summary = diags.display_warnings_summary().to_string();
+  assert!(summary.ends_with(" 2 unused-definition\n"), "{summary}");
+
+  let allowed = Diagnostics::new(DiagnosticsConfig::new(Severity::Allow, false));
+  assert_eq!(allowed.display_warnings_summary().to_string(), "");
+}
+
+#[test]
+fn parse_error_diagnostic() {
+  let code = "main = 1\n\nfoo = (bar baz\n";
+  let diags =
+    crate::fun::load_book::do_parse_book(code, "foo.bend".as_ref(), Default::default()).unwrap_err();
+
+  let errs = &diags.diagnostics[&DiagnosticOrigin::Parsing(PathBuf::from("foo.bend"))];
+  assert_eq!(errs[0].parse_error_kind(), Some(ParseErrorKind::UnexpectedEof));
+  let span = errs[0].span().unwrap();
+  assert_eq!((span.start, span.line, span.column), (code.len(), 4, 1));
+  assert!(diags.to_string().contains("In foo.bend :\n"));
+}
@@ -0,0 +1,314 @@
+use crate::{
+  fun::{Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+impl Term {
+  /// Checks if two terms are equal up to the renaming of their bound variables.
+  ///
+  /// Free variables and references must have the same names,
+  /// and all the tags (including dup labels) must be the same.
+  /// Unscoped variables can be renamed, as long as it's done consistently in the whole term.
+  ///
+  /// Example:
+  /// ```hvm
+  /// // Equal:
+  /// λx λy (x y)  ~  λa λb (a b)
+  /// λ$x (f $x)   ~  λ$y (f $y)
+  ///
+  /// // Not equal:
+  /// λx λy (x y)  ~  λx λy (y x)  // Different binders
+  /// λx (f x)     ~  λx (g x)     // Different free vars
+  /// ```
+  pub fn alpha_eq(&self, other: &Term) -> bool {
+    AlphaEq::default().term(self, other)
+  }
+
+  /// Checks if two readback results are the same value, to compare the results of running programs.
+  ///
+  /// Like [Term::alpha_eq], but lists, strings and natural numbers are compared in their constructor form,
+  /// so a resugared result is equal to the same result read back without resugaring.
+  /// Numbers are equal if they have the same type and the same value in the runtime.
+  /// A free variable is equal to a reference with the same name, so the result can be compared
+  /// with a term that was parsed but not compiled, where constructors are still variables.
+  ///
+  /// Example:
+  /// ```hvm
+  /// // Equal:
+  /// [λx x, "a"]  ~  (List/Cons λy y (List/Cons (String/Cons 97 String/Nil) List/Nil))
+  /// ```
+  pub fn result_eq(&self, other: &Term) -> bool {
+    let mut lft = self.clone();
+    let mut rgt = other.clone();
+    lft.encode_builtins();
+    rgt.encode_builtins();
+    AlphaEq { free_vars_as_refs: true, ..Default::default() }.term(&lft, &rgt)
+  }
+}
+
+#[derive(Default)]
+struct AlphaEq {
+  /// The binder that each variable in scope refers to, for each of the terms.
+  lft_scope: HashMap<Name, Vec<usize>>,
+  rgt_scope: HashMap<Name, Vec<usize>>,
+  /// Counter to give each pair of binders a unique id.
+  binders: usize,
+  /// One-to-one correspondence between the unscoped variables of each term.
+  lft_links: HashMap<Name, Name>,
+  rgt_links: HashMap<Name, Name>,
+  /// Whether a free variable is equal to a reference with the same name.
+  free_vars_as_refs: bool,
+}
+
+impl AlphaEq {
+  fn term(&mut self, lft: &Term, rgt: &Term) -> bool {
+    maybe_grow(|| match (lft, rgt) {
+      (Term::Lam { tag: t1, pat: p1, bod: b1 }, Term::Lam { tag: t2, pat: p2, bod: b2 }) => {
+        t1 == t2 && self.pat(p1, p2) && self.with_binds(p1.binds(), p2.binds(), |slf| slf.term(b1, b2))
+      }
+      (Term::Var { nam: n1 }, Term::Var { nam: n2 }) => self.var(n1, n2),
+      (Term::Link { nam: n1 }, Term::Link { nam: n2 }) => self.link(n1, n2),
+      (Term::Let { pat: p1, val: v1, nxt: n1 }, Term::Let { pat: p2, val: v2, nxt: n2 })
+      | (Term::Ask { pat: p1, val: v1, nxt: n1 }, Term::Ask { pat: p2, val: v2, nxt: n2 }) => {
+        self.term(v1, v2)
+          && self.pat(p1, p2)
+          && self.with_binds(p1.binds(), p2.binds(), |slf| slf.term(n1, n2))
+      }
+      (Term::Use { nam: a1, val: v1, nxt: n1 }, Term::Use { nam: a2, val: v2, nxt: n2 }) => {
+        self.term(v1, v2) && self.with_binds([a1], [a2], |slf| slf.term(n1, n2))
+      }
+      (Term::Do { typ: t1, bod: b1 }, Term::Do { typ: t2, bod: b2 }) => t1 == t2 && self.term(b1, b2),
+      (Term::App { tag: t1, fun: f1, arg: a1 }, Term::App { tag: t2, fun: f2, arg: a2 }) => {
+        t1 == t2 && self.term(f1, f2) && self.term(a1, a2)
+      }
+      (Term::Fan { fan: f1, tag: t1, els: e1 }, Term::Fan { fan: f2, tag: t2, els: e2 }) => {
+        f1 == f2 && t1 == t2 && self.terms(e1, e2)
+      }
+      (Term::List { els: e1 }, Term::List { els: e2 }) => self.terms(e1, e2),
+      (Term::Oper { opr: o1, fst: f1, snd: s1 }, Term::Oper { opr: o2, fst: f2, snd: s2 }) => {
+        o1 == o2 && self.term(f1, f2) && self.term(s1, s2)
+      }
+      (
+        Term::Mat { arg: a1, bnd: b1, with: w1, arms: r1 },
+        Term::Mat { arg: a2, bnd: b2, with: w2, arms: r2 },
+      )
+      | (
+        Term::Fold { arg: a1, bnd: b1, with: w1, arms: r1 },
+        Term::Fold { arg: a2, bnd: b2, with: w2, arms: r2 },
+      ) => {
+        // The bound name of the matched value is in scope in the arms, along with the fields.
+        self.term(a1, a2)
+          && self.vars(w1, w2)
+          && r1.len() == r2.len()
+          && self.with_binds([b1], [b2], |slf| {
+            r1.iter()
+              .zip(r2)
+              .all(|((c1, f1, t1), (c2, f2, t2))| c1 == c2 && slf.with_binds(f1, f2, |slf| slf.term(t1, t2)))
+          })
+      }
+      (
+        Term::Swt { arg: a1, bnd: b1, with: w1, pred: p1, arms: r1 },
+        Term::Swt { arg: a2, bnd: b2, with: w2, pred: p2, arms: r2 },
+      ) => {
+        self.term(a1, a2)
+          && self.vars(w1, w2)
+          && r1.len() == r2.len()
+          && self.with_binds([b1], [b2], |slf| {
+            let (Some((s1, n1)), Some((s2, n2))) = (r1.split_last(), r2.split_last()) else {
+              return true;
+            };
+            slf.terms(n1, n2) && slf.with_binds([p1], [p2], |slf| slf.term(s1, s2))
+          })
+      }
+      (
+        Term::Bend { bind: b1, init: i1, cond: c1, step: s1, base: e1 },
+        Term::Bend { bind: b2, init: i2, cond: c2, step: s2, base: e2 },
+      ) => {
+        self.terms(i1, i2)
+          && self.with_binds(b1, b2, |slf| slf.term(c1, c2) && slf.term(s1, s2) && slf.term(e1, e2))
+      }
+      (Term::Open { typ: t1, var: v1, bod: b1 }, Term::Open { typ: t2, var: v2, bod: b2 }) => {
+        t1 == t2 && self.var(v1, v2) && self.term(b1, b2)
+      }
+      (Term::Num { val: v1 }, Term::Num { val: v2 }) => v1 == v2,
+      (Term::Nat { val: v1 }, Term::Nat { val: v2 }) => v1 == v2,
+      (Term::Str { val: v1 }, Term::Str { val: v2 }) => v1 == v2,
+      (Term::Ref { nam: n1 }, Term::Ref { nam: n2 }) => n1 == n2,
+      (Term::Var { nam: var }, Term::Ref { nam: r#ref }) if self.free_vars_as_refs => {
+        var == r#ref && !self.lft_scope.get(var).is_some_and(|b| !b.is_empty())
+      }
+      (Term::Ref { nam: r#ref }, Term::Var { nam: var }) if self.free_vars_as_refs => {
+        var == r#ref && !self.rgt_scope.get(var).is_some_and(|b| !b.is_empty())
+      }
+      (Term::Era, Term::Era) => true,
+      (Term::Err, Term::Err) => true,
+      _ => false,
+    })
+  }
+
+  fn terms(&mut self, lft: &[Term], rgt: &[Term]) -> bool {
+    lft.len() == rgt.len() && lft.iter().zip(rgt).all(|(l, r)| self.term(l, r))
+  }
+
+  /// Compares the structure of two patterns, ignoring the names of the variables they bind.
+  fn pat(&mut self, lft: &Pattern, rgt: &Pattern) -> bool {
+    maybe_grow(|| match (lft, rgt) {
+      (Pattern::Var(n1), Pattern::Var(n2)) => n1.is_some() == n2.is_some(),
+      (Pattern::Chn(n1), Pattern::Chn(n2)) => self.link(n1, n2),
+      (Pattern::Ctr(c1, p1), Pattern::Ctr(c2, p2)) => c1 == c2 && self.pats(p1, p2),
+      (Pattern::Num(n1), Pattern::Num(n2)) => n1 == n2,
+      (Pattern::Fan(f1, t1, p1), Pattern::Fan(f2, t2, p2)) => f1 == f2 && t1 == t2 && self.pats(p1, p2),
+      (Pattern::Lst(p1), Pattern::Lst(p2)) => self.pats(p1, p2),
+      (Pattern::Str(s1), Pattern::Str(s2)) => s1 == s2,
+      _ => false,
+    })
+  }
+
+  fn pats(&mut self, lft: &[Pattern], rgt: &[Pattern]) -> bool {
+    lft.len() == rgt.len() && lft.iter().zip(rgt).all(|(l, r)| self.pat(l, r))
+  }
+
+  /// Two variables are equal if they refer to the same pair of binders,
+  /// or if they're both free and have the same name.
+  fn var(&self, lft: &Name, rgt: &Name) -> bool {
+    let lft_bind = self.lft_scope.get(lft).and_then(|b| b.last());
+    let rgt_bind = self.rgt_scope.get(rgt).and_then(|b| b.last());
+    match (lft_bind, rgt_bind) {
+      (Some(b1), Some(b2)) => b1 == b2,
+      (None, None) => lft == rgt,
+      _ => false,
+    }
+  }
+
+  fn vars(&self, lft: &[Name], rgt: &[Name]) -> bool {
+    lft.len() == rgt.len() && lft.iter().zip(rgt).all(|(l, r)| self.var(l, r))
+  }
+
+  /// Unscoped variables are equal if they were always paired with each other.
+  fn link(&mut self, lft: &Name, rgt: &Name) -> bool {
+    let lft_link = self.lft_links.entry(lft.clone()).or_insert_with(|| rgt.clone());
+    let rgt_link = self.rgt_links.entry(rgt.clone()).or_insert_with(|| lft.clone());
+    lft_link == rgt && rgt_link == lft
+  }
+
+  /// Runs `f` with the given binds in scope.
+  /// Each bind on the left side is paired with the bind in the same position on the right side.
+  fn with_binds<'a>(
+    &mut self,
+    lft: impl IntoIterator<Item = &'a Option<Name>>,
+    rgt: impl IntoIterator<Item = &'a Option<Name>>,
+    f: impl FnOnce(&mut Self) -> bool,
+  ) -> bool {
+    let lft = lft.into_iter().collect::<Vec<_>>();
+    let rgt = rgt.into_iter().collect::<Vec<_>>();
+    if lft.len() != rgt.len() || lft.iter().zip(&rgt).any(|(l, r)| l.is_some() != r.is_some()) {
+      return false;
+    }
+
+    for (lft, rgt) in lft.iter().zip(&rgt) {
+      if let (Some(lft), Some(rgt)) = (lft, rgt) {
+        self.lft_scope.entry(lft.clone()).or_default().push(self.binders);
+        self.rgt_scope.entry(rgt.clone()).or_default().push(self.binders);
+        self.binders += 1;
+      }
+    }
+
+    let res = f(self);
+
+    for (lft, rgt) in lft.iter().zip(&rgt) {
+      if let (Some(lft), Some(rgt)) = (lft, rgt) {
+        self.lft_scope.get_mut(lft).unwrap().pop();
+        self.rgt_scope.get_mut(rgt).unwrap().pop();
+      }
+    }
+
+    res
+  }
+}
+
+#[test]
+fn alpha_eq() {
+  use crate::fun::{parser::TermParser, FanKind, Tag};
+
+  fn eq(a: &str, b: &str) -> bool {
+    let a = TermParser::new(a).parse_term().unwrap();
+    let b = TermParser::new(b).parse_term().unwrap();
+    a.alpha_eq(&b) && b.alpha_eq(&a)
+  }
+
+  assert!(eq("λx λy (x y)", "λa λb (a b)"));
+  assert!(eq("λx λx x", "λa λb b"));
+  assert!(eq("let (a, b) = x; (b a)", "let (c, d) = x; (d c)"));
+  assert!(eq("λ$x (f $x)", "λ$y (f $y)"));
+
+  // Binding a name must not capture a free variable with the same name.
+  assert!(!eq("λx λy (x y)", "λx λy (y x)"));
+  assert!(!eq("λx λx x", "λa λb a"));
+  assert!(!eq("λx y", "λy y"));
+  assert!(!eq("λx (f x)", "λx (g x)"));
+  assert!(!eq("λx (Foo x)", "λx (Bar x)"));
+  assert!(!eq("λ* x", "λx x"));
+
+  // The bound name of a match is in scope in its arms.
+  assert!(eq(
+    "λa match x = a { List/Nil: x; List/Cons: (f x) }",
+    "λb match y = b { List/Nil: y; List/Cons: (f y) }"
+  ));
+  assert!(eq("λa switch x = a { 0: x; _: x-1 }", "λb switch y = b { 0: y; _: y-1 }"));
+  assert!(!eq(
+    "λa match x = a { List/Nil: x; List/Cons: (f x) }",
+    "λb match y = b { List/Nil: b; List/Cons: (f y) }"
+  ));
+  let swt = |arg: &str| Term::Swt {
+    arg: Box::new(Term::Var { nam: Name::new(arg) }),
+    bnd: None,
+    with: vec![],
+    pred: None,
+    arms: vec![],
+  };
+  assert!(!swt("x").alpha_eq(&swt("y")));
+
+  // Unscoped variables must be renamed consistently.
+  assert!(!eq("λ$x λ$y ($x $y)", "λ$a λ$b ($a $a)"));
+  assert!(!eq("λ$x λ$y ($x $y)", "λ$a λ$a ($a $a)"));
+
+  // Dups with different labels are not equal.
+  fn dup(tag: Tag, fst: &str, snd: &str) -> Term {
+    let (fst, snd) = (Name::new(fst), Name::new(snd));
+    Term::Let {
+      pat: Box::new(Pattern::Fan(FanKind::Dup, tag, vec![
+        Pattern::Var(Some(fst.clone())),
+        Pattern::Var(Some(snd.clone())),
+      ])),
+      val: Box::new(Term::Var { nam: Name::new("z") }),
+      nxt: Box::new(Term::app(Term::Var { nam: fst }, Term::Var { nam: snd })),
+    }
+  }
+  assert!(dup(Tag::Numeric(1), "x", "y").alpha_eq(&dup(Tag::Numeric(1), "a", "b")));
+  assert!(!dup(Tag::Numeric(1), "x", "y").alpha_eq(&dup(Tag::Numeric(2), "x", "y")));
+  assert!(!dup(Tag::Auto, "x", "y").alpha_eq(&dup(Tag::Numeric(1), "x", "y")));
+}
+
+#[test]
+fn result_eq() {
+  use crate::fun::parser::TermParser;
+
+  fn eq(a: &str, b: &str) -> bool {
+    let a = TermParser::new(a).parse_term().unwrap();
+    let b = TermParser::new(b).parse_term().unwrap();
+    a.result_eq(&b) && b.result_eq(&a)
+  }
+
+  assert!(eq("[λx x, (1, λy λz (z y))]", "[λa a, (1, λb λc (c b))]"));
+  assert!(eq("[1, 2]", "(List/Cons 1 (List/Cons 2 List/Nil))"));
+  assert!(eq(r#"["ab", λx x]"#, "(List/Cons (String/Cons 97 (String/Cons 98 String/Nil)) [λy y])"));
+  let nat = TermParser::new("(Nat/Succ (Nat/Succ Nat/Zero))").parse_term().unwrap();
+  assert!(Term::Nat { val: 2 }.result_eq(&nat));
+
+  assert!(!eq("[1, 2]", "[2, 1]"));
+  assert!(!eq("[λx λy x]", "[λx λy y]"));
+  assert!(!eq("\"ab\"", "[97, 98]"));
+  assert!(!eq("1", "+1"));
+  assert!(!eq("1", "1.0"));
+}
@@ -1,5 +1,6 @@
 use super::{parser::TermParser, Book, Name, Num, Pattern, Term};
 use crate::maybe_grow;
+use std::sync::OnceLock;
 
 const BUILTINS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/fun/builtins.bend"));
 
@@ -22,11 +23,25 @@ pub const NAT: &str = "Nat";
 pub const NAT_SUCC: &str = "Nat/Succ";
 pub const NAT_ZERO: &str = "Nat/Zero";
 
+/// The builtins book, parsed the first time it's needed, see [Book::parsed_builtins].
+static PARSED_BUILTINS: OnceLock<Book> = OnceLock::new();
+
 impl Book {
+  /// A book with the builtin definitions and datatypes, to parse a program on top of.
   pub fn builtins() -> Book {
-    TermParser::new(BUILTINS)
-      .parse_book(Book::default(), true)
-      .expect("Error parsing builtin file, this should not happen")
+    Book::parsed_builtins().clone()
+  }
+
+  /// The builtins book, parsed only once and shared by every later call.
+  ///
+  /// Only the parsing is cached. The builtins are still desugared and compiled
+  /// together with each program, since that depends on the program and on the options.
+  pub fn parsed_builtins() -> &'static Book {
+    PARSED_BUILTINS.get_or_init(|| {
+      TermParser::new(BUILTINS)
+        .parse_book(Book::default(), true)
+        .expect("Error parsing builtin file, this should not happen")
+    })
   }
 
   pub fn encode_builtins(&mut self) {
@@ -40,7 +55,7 @@ impl Book {
 }
 
 impl Term {
-  fn encode_builtins(&mut self) {
+  pub fn encode_builtins(&mut self) {
     maybe_grow(|| match self {
       Term::List { els } => *self = Term::encode_list(std::mem::take(els)),
       Term::Str { val } => *self = Term::encode_str(val),
@@ -110,3 +125,15 @@ impl Pattern {
     })
   }
 }
+
+#[test]
+fn builtins_are_copied() {
+  let mut book = Book::builtins();
+  let n_defs = book.defs.len();
+  book.defs.clear();
+  book.adts.clear();
+  let book = Book::builtins();
+  assert_eq!(book.defs.len(), n_defs);
+  assert_eq!(Book::parsed_builtins().defs.len(), n_defs);
+  assert!(book.adts.contains_key(&Name::new(LIST)));
+}
@@ -0,0 +1,57 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, Num, Op, Term},
+  maybe_grow,
+};
+
+impl Ctx<'_> {
+  /// Warns about `==` and `!=` comparisons between floats,
+  /// which are almost never what's wanted since float operations round their results.
+  ///
+  /// Without type information, an operand is only known to be a float
+  /// if it's a float literal or an arithmetic operation with a float operand.
+  pub fn check_float_equality(&mut self) {
+    for (def_name, def) in &self.book.defs {
+      for rule in &def.rules {
+        let mut oprs = vec![];
+        rule.body.float_equalities(&mut oprs);
+        for opr in oprs {
+          self.info.add_rule_warning(
+            format!(
+              "Comparing floats with '{opr}' is unreliable because of rounding errors, consider checking if their difference is smaller than some epsilon instead."
+            ),
+            WarningType::FloatEquality,
+            def_name.clone(),
+          );
+        }
+      }
+    }
+  }
+}
+
+impl Term {
+  /// Collects the equality operators of the comparisons with a known float operand.
+  fn float_equalities(&self, oprs: &mut Vec<Op>) {
+    maybe_grow(|| {
+      if let Term::Oper { opr: opr @ (Op::EQL | Op::NEQ), fst, snd } = self
+        && (fst.is_known_float() || snd.is_known_float())
+      {
+        oprs.push(*opr);
+      }
+      for child in self.children() {
+        child.float_equalities(oprs);
+      }
+    })
+  }
+
+  fn is_known_float(&self) -> bool {
+    maybe_grow(|| match self {
+      Term::Num { val: Num::F24(_) } => true,
+      Term::Oper { opr, fst, snd } => {
+        matches!(opr, Op::ADD | Op::SUB | Op::MUL | Op::DIV | Op::REM | Op::POW | Op::ATN | Op::LOG)
+          && (fst.is_known_float() || snd.is_known_float())
+      }
+      _ => false,
+    })
+  }
+}
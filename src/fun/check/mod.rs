@@ -1,3 +1,8 @@
+pub mod float_equality;
+pub mod never_evaluated;
+pub mod operations;
+pub mod partial_application;
 pub mod set_entrypoint;
+pub mod shadowed_vars;
 pub mod shared_names;
 pub mod unbound_vars;
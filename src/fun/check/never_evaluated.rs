@@ -0,0 +1,121 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, Name, Num, Pattern, Term},
+  maybe_grow,
+};
+use indexmap::IndexSet;
+use std::collections::HashSet;
+
+impl Ctx<'_> {
+  /// Warns about definitions that are reachable from the entrypoint,
+  /// but that are only referenced in places where they always get erased.
+  ///
+  /// Since HVM erases references without expanding them, these definitions never get evaluated.
+  ///
+  /// This is only a heuristic, it recognizes the most common erased places:
+  /// the value of a `let *`, the argument of a lambda that discards it,
+  /// and the arms of a `switch` on a number literal that are never selected.
+  /// Only a reference that is erased by itself counts, since any other erased term,
+  /// like an application of the definition, is still reduced.
+  /// Unused definitions are reported separately, by the pruning pass.
+  ///
+  /// Must be called after `linearize_vars`, so that unused binds are already erased.
+  pub fn check_never_evaluated(&mut self) {
+    let Some(main) = &self.book.entrypoint else { return };
+
+    // Definitions reached through non-erased places, starting from the entrypoint.
+    let mut evaluated = HashSet::from([main.clone()]);
+    let mut erased = IndexSet::new();
+    let mut to_visit = vec![main.clone()];
+    while let Some(def_name) = to_visit.pop() {
+      let Some(def) = self.book.defs.get(&def_name) else { continue };
+      for rule in &def.rules {
+        let mut refs = vec![];
+        rule.body.evaluated_refs(true, &mut refs);
+        for (nam, is_evaluated) in refs {
+          if !is_evaluated {
+            erased.insert(nam);
+          } else if evaluated.insert(nam.clone()) {
+            to_visit.push(nam);
+          }
+        }
+      }
+    }
+
+    // Definitions only reached through erased places, and everything they reference.
+    let mut never_evaluated = IndexSet::new();
+    let mut to_visit = erased.into_iter().collect::<Vec<_>>();
+    while let Some(def_name) = to_visit.pop() {
+      if evaluated.contains(&def_name) || !never_evaluated.insert(def_name.clone()) {
+        continue;
+      }
+      let Some(def) = self.book.defs.get(&def_name) else { continue };
+      for rule in &def.rules {
+        let mut refs = vec![];
+        rule.body.evaluated_refs(true, &mut refs);
+        to_visit.extend(refs.into_iter().map(|(nam, _)| nam));
+      }
+    }
+
+    for def_name in never_evaluated {
+      let Some(def) = self.book.defs.get(&def_name) else { continue };
+      if def.builtin || def_name.is_generated() || self.book.ctrs.contains_key(&def_name) {
+        continue;
+      }
+      self.info.add_rule_warning(
+        "Definition is never evaluated, it's only referenced in places where it gets erased.",
+        WarningType::NeverEvaluated,
+        def_name,
+      );
+    }
+  }
+}
+
+impl Term {
+  /// Collects the references in this term, along with whether they're in a place that may get evaluated.
+  fn evaluated_refs(&self, evaluated: bool, refs: &mut Vec<(Name, bool)>) {
+    maybe_grow(|| match self {
+      Term::Ref { nam } => refs.push((nam.clone(), evaluated)),
+      Term::Let { pat: box Pattern::Var(None), val, nxt } => {
+        val.erased_refs(evaluated, refs);
+        nxt.evaluated_refs(evaluated, refs);
+      }
+      Term::App { fun: box Term::Lam { pat: box Pattern::Var(None), bod, .. }, arg, .. } => {
+        bod.evaluated_refs(evaluated, refs);
+        arg.erased_refs(evaluated, refs);
+      }
+      Term::Swt { arg: box Term::Num { val: Num::U24(val) }, arms, .. } if !arms.is_empty() => {
+        let selected = (*val as usize).min(arms.len() - 1);
+        for (i, arm) in arms.iter().enumerate() {
+          if i == selected {
+            arm.evaluated_refs(evaluated, refs);
+          } else if let Term::Lam { pat: box Pattern::Var(_), bod, .. } = arm
+            && i != 0
+          {
+            // After encoding, the successor arm binds the predecessor with a lambda.
+            bod.erased_refs(evaluated, refs);
+          } else {
+            arm.erased_refs(evaluated, refs);
+          }
+        }
+      }
+      _ => {
+        for child in self.children() {
+          child.evaluated_refs(evaluated, refs);
+        }
+      }
+    })
+  }
+
+  /// Collects the references of a term that gets erased.
+  ///
+  /// Only a reference by itself is erased without being expanded.
+  /// Any other term is still reduced strictly before being erased,
+  /// so the references inside it may get evaluated.
+  fn erased_refs(&self, evaluated: bool, refs: &mut Vec<(Name, bool)>) {
+    match self {
+      Term::Ref { nam } => refs.push((nam.clone(), false)),
+      _ => self.evaluated_refs(evaluated, refs),
+    }
+  }
+}
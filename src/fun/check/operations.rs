@@ -0,0 +1,80 @@
+use crate::{
+  diagnostics::Diagnostics,
+  fun::{Book, Ctx, Op, Term},
+  maybe_grow,
+};
+use std::collections::{BTreeSet, HashSet};
+
+impl Book {
+  /// The numeric operations that appear in the definitions of the book.
+  ///
+  /// After `desugar_book`, only the builtins that the program references are left,
+  /// so the operations of the unused ones aren't counted.
+  pub fn used_operations(&self) -> HashSet<Op> {
+    let mut ops = HashSet::new();
+    for def in self.defs.values() {
+      for rule in &def.rules {
+        rule.body.used_operations(&mut ops);
+      }
+    }
+    ops
+  }
+}
+
+impl Ctx<'_> {
+  /// Checks that the book only uses the numeric operations in `allowed`,
+  /// for running it on a runtime that doesn't support all of them.
+  ///
+  /// Reports each disallowed operation once for each definition that uses it.
+  pub fn check_operations(&mut self, allowed: &HashSet<Op>) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for (def_name, def) in &self.book.defs {
+      let mut ops = HashSet::new();
+      for rule in &def.rules {
+        rule.body.used_operations(&mut ops);
+      }
+      // Sorted by their symbol so that the errors have a stable order.
+      let disallowed = ops.difference(allowed).map(|op| op.to_string()).collect::<BTreeSet<_>>();
+      for op in disallowed {
+        self
+          .info
+          .add_rule_error(format!("Operation '{op}' is not supported by the target."), def_name.clone());
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  fn used_operations(&self, ops: &mut HashSet<Op>) {
+    maybe_grow(|| {
+      if let Term::Oper { opr, .. } = self {
+        ops.insert(*opr);
+      }
+      for child in self.children() {
+        child.used_operations(ops);
+      }
+    })
+  }
+}
+
+#[test]
+fn used_operations() {
+  use crate::{diagnostics::DiagnosticsConfig, fun::parser::TermParser};
+
+  let mut book = TermParser::new("foo = λx (* x 2)\nmain = (+ (foo 1) (/ 6 (foo 2)))")
+    .parse_book(Book::default(), false)
+    .unwrap();
+  assert_eq!(book.used_operations(), HashSet::from([Op::ADD, Op::MUL, Op::DIV]));
+
+  let mut ctx = Ctx::new(&mut book, DiagnosticsConfig::default());
+  assert!(ctx.check_operations(&HashSet::from([Op::ADD, Op::MUL, Op::DIV, Op::SUB])).is_ok());
+
+  let mut ctx = Ctx::new(&mut book, DiagnosticsConfig::default());
+  let err = ctx.check_operations(&HashSet::from([Op::ADD, Op::SUB])).unwrap_err().to_string();
+  assert!(err.contains("In definition '\u{1b}[4mfoo\u{1b}[0m\u{1b}[1m'"));
+  assert!(err.contains("Operation '/' is not supported by the target."));
+  assert_eq!(err.matches("is not supported by the target").count(), 2);
+}
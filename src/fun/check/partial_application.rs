@@ -0,0 +1,70 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, Name, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+impl Ctx<'_> {
+  /// Warns about applications of a definition to fewer arguments than its rules take.
+  ///
+  /// This is opt-in, since partial application is usually intentional.
+  /// Only the arguments written as rule patterns count for the arity,
+  /// so definitions written with lambdas are never reported.
+  ///
+  /// Must be called after `resolve_refs` and before `desugar_match_defs`,
+  /// while the rules still have their patterns.
+  pub fn check_partial_applications(&mut self) {
+    let arities =
+      self.book.defs.iter().map(|(name, def)| (name.clone(), def.arity())).collect::<HashMap<_, _>>();
+
+    for (def_name, def) in &self.book.defs {
+      for rule in &def.rules {
+        let mut partial = vec![];
+        rule.body.partial_applications(&arities, &mut partial);
+        for (nam, expected, provided) in partial {
+          self.info.add_rule_warning(
+            format!("Definition '{nam}' takes {expected} arguments, but is applied to only {provided}."),
+            WarningType::PartialApplication,
+            def_name.clone(),
+          );
+        }
+      }
+    }
+  }
+}
+
+impl Term {
+  /// Collects the applications of known definitions to fewer arguments than their arity.
+  fn partial_applications(&self, arities: &HashMap<Name, usize>, partial: &mut Vec<(Name, usize, usize)>) {
+    maybe_grow(|| {
+      if let Term::App { .. } = self {
+        let mut fun = self;
+        let mut provided = 0;
+        while let Term::App { fun: app_fun, .. } = fun {
+          fun = app_fun;
+          provided += 1;
+        }
+        if let Term::Ref { nam } = fun
+          && let Some(&expected) = arities.get(nam)
+          && provided < expected
+        {
+          partial.push((nam.clone(), expected, provided));
+        }
+
+        // Visit the arguments of the whole spine, skipping the nested applications.
+        let mut app = self;
+        while let Term::App { fun: app_fun, arg, .. } = app {
+          arg.partial_applications(arities, partial);
+          app = app_fun;
+        }
+        app.partial_applications(arities, partial);
+        return;
+      }
+
+      for child in self.children() {
+        child.partial_applications(arities, partial);
+      }
+    })
+  }
+}
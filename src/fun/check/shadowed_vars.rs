@@ -0,0 +1,63 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+impl Ctx<'_> {
+  /// Warns about variables bound with the same name as another variable already in scope,
+  /// which makes the outer variable inaccessible in the body of the inner binder.
+  ///
+  /// This is opt-in, since shadowing is often intentional, like when rebinding a variable with `let`.
+  /// Variables whose names start with `_` are never reported.
+  ///
+  /// Must be called after `resolve_refs` and before `desugar_match_defs`,
+  /// so that only the binders written by the user are checked.
+  pub fn check_shadowed_vars(&mut self) {
+    for (def_name, def) in &self.book.defs {
+      if def.builtin {
+        continue;
+      }
+      for rule in &def.rules {
+        let mut scope = HashMap::new();
+        for nam in rule.pats.iter().flat_map(Pattern::binders) {
+          *scope.entry(nam).or_default() += 1;
+        }
+        let mut shadowed = vec![];
+        rule.body.shadowed_vars(&mut scope, &mut shadowed);
+        for nam in shadowed {
+          self.info.add_rule_warning(
+            format!("Variable '{nam}' shadows another variable with the same name."),
+            WarningType::ShadowedVariable,
+            def_name.clone(),
+          );
+        }
+      }
+    }
+  }
+}
+
+impl Term {
+  /// Collects the variables bound while another variable with the same name is in scope.
+  fn shadowed_vars<'a>(&'a self, scope: &mut HashMap<&'a Name, usize>, shadowed: &mut Vec<Name>) {
+    maybe_grow(|| {
+      for (child, binds) in self.children_with_binds() {
+        let binds = binds.flatten().collect::<Vec<_>>();
+        // Checked before binding any of them, since repeating a name in the same pattern is a different error.
+        for nam in &binds {
+          if scope.get(nam).is_some_and(|&count| count > 0) && !nam.starts_with('_') {
+            shadowed.push((*nam).clone());
+          }
+        }
+        for nam in &binds {
+          *scope.entry(nam).or_default() += 1;
+        }
+        child.shadowed_vars(scope, shadowed);
+        for nam in binds {
+          *scope.get_mut(nam).unwrap() -= 1;
+        }
+      }
+    })
+  }
+}
@@ -1,5 +1,5 @@
 use crate::fun::{Ctx, Name};
-use indexmap::IndexMap;
+use indexmap::{map::Entry, IndexMap};
 use std::fmt::Display;
 
 #[derive(Debug, Clone)]
@@ -9,6 +9,13 @@ pub struct RepeatedTopLevelNameErr {
   name: Name,
 }
 
+#[derive(Debug, Clone)]
+pub struct RepeatedCtrErr {
+  ctr: Name,
+  adt_fst: Name,
+  adt_snd: Name,
+}
+
 impl Ctx<'_> {
   /// Checks if there are any repeated top level names. Constructors
   /// and functions can't share names and adts can't share names.
@@ -30,6 +37,21 @@ impl Ctx<'_> {
     for err in names.into_errs() {
       self.info.add_book_error(err);
     }
+
+    // The parser rejects these, but books can also be built directly.
+    let mut ctr_adts = IndexMap::<&Name, &Name>::new();
+    for (adt_name, adt) in &self.book.adts {
+      for ctr_name in adt.ctrs.keys() {
+        match ctr_adts.entry(ctr_name) {
+          Entry::Vacant(e) => _ = e.insert(adt_name),
+          Entry::Occupied(e) => self.info.add_book_error(RepeatedCtrErr {
+            ctr: ctr_name.clone(),
+            adt_fst: (*e.get()).clone(),
+            adt_snd: adt_name.clone(),
+          }),
+        }
+      }
+    }
   }
 }
 
@@ -91,3 +113,33 @@ impl std::fmt::Display for RepeatedTopLevelNameErr {
     write!(f, "{} '{}' has the same name as a previously defined {}", snd, self.name, self.kind_fst)
   }
 }
+
+impl std::fmt::Display for RepeatedCtrErr {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Constructor '{}' is declared by both data types '{}' and '{}'",
+      self.ctr, self.adt_fst, self.adt_snd
+    )
+  }
+}
+
+#[test]
+fn repeated_ctr_in_adts() {
+  use crate::{
+    diagnostics::DiagnosticsConfig,
+    fun::{Adt, Book},
+  };
+
+  let mut book = Book::default();
+  for (adt_name, ctr_name) in [("A", "A/B/C"), ("A/B", "A/B/C")] {
+    let adt = Adt { ctrs: [(Name::new(ctr_name), vec![])].into_iter().collect(), builtin: false };
+    book.adts.insert(Name::new(adt_name), adt);
+    book.ctrs.insert(Name::new(ctr_name), Name::new(adt_name));
+  }
+
+  let mut ctx = Ctx::new(&mut book, DiagnosticsConfig::default());
+  ctx.check_shared_names();
+  let err = ctx.info.to_string();
+  assert!(err.contains("Constructor 'A/B/C' is declared by both data types 'A' and 'A/B'"));
+}
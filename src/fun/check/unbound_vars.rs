@@ -3,20 +3,47 @@ use crate::{
   fun::{Ctx, Name, Pattern, Term},
   maybe_grow,
 };
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 pub enum UnboundVarErr {
-  Local(Name),
-  Global { var: Name, declared: usize, used: usize },
+  /// `unused_def` is whether the suggestion is a definition that is not used anywhere else.
+  Local {
+    var: Name,
+    suggestion: Option<Name>,
+    unused_def: bool,
+  },
+  Global {
+    var: Name,
+    declared: usize,
+    used: usize,
+  },
+  NonLinearPattern(Name),
 }
 
 impl Ctx<'_> {
   /// Checks that there are no unbound variables in all definitions.
+  ///
+  /// Definitions that already have a [`Term::Err`] from an error in a previous pass
+  /// are not analysed, reporting a single error instead of the ones caused by the first.
+  ///
+  /// Unbound variables suggest the closest variable in scope or definition name, if any is similar enough.
+  /// When the suggestion is a definition that is not referenced anywhere else,
+  /// the error says so, since the typo is likely also why that definition is unused.
   pub fn check_unbound_vars(&mut self) -> Result<(), Diagnostics> {
     self.info.start_pass();
 
+    let def_names = self.book.defs.keys().filter(|nam| !nam.is_generated()).cloned().collect::<Vec<_>>();
+
+    let mut def_errs = Vec::new();
     for (def_name, def) in self.book.defs.iter_mut() {
+      if def.rules.iter().any(|rule| rule.body.has_err()) {
+        self
+          .info
+          .add_rule_error("A previous error prevented the analysis of this definition.", def_name.clone());
+        continue;
+      }
+
       let mut errs = Vec::new();
       for rule in &mut def.rules {
         let mut scope = HashMap::new();
@@ -24,29 +51,72 @@ impl Ctx<'_> {
           pat.binds().for_each(|nam| push_scope(nam.as_ref(), &mut scope));
         }
 
-        rule.body.check_unbound_vars(&mut scope, &mut errs);
+        rule.body.check_unbound_vars(&mut scope, &def_names, &mut errs);
       }
 
-      for err in errs {
+      def_errs.push((def_name.clone(), errs));
+    }
+
+    let has_suggestions = def_errs
+      .iter()
+      .flat_map(|(_, errs)| errs)
+      .any(|err| matches!(err, UnboundVarErr::Local { suggestion: Some(_), .. }));
+    let unused_defs = if has_suggestions { self.unused_defs() } else { HashSet::new() };
+
+    for (def_name, errs) in def_errs {
+      for mut err in errs {
+        if let UnboundVarErr::Local { suggestion: Some(suggestion), unused_def, .. } = &mut err {
+          *unused_def = unused_defs.contains(suggestion);
+        }
         self.info.add_rule_error(err, def_name.clone());
       }
     }
 
     self.info.fatal(())
   }
+
+  /// The user definitions that are not referenced by any other definition, nor are the entrypoint.
+  fn unused_defs(&self) -> HashSet<Name> {
+    let mut unused = self
+      .book
+      .defs
+      .values()
+      .filter(|def| !def.builtin && !self.book.ctrs.contains_key(&def.name))
+      .map(|def| def.name.clone())
+      .collect::<HashSet<_>>();
+    for def in self.book.defs.values() {
+      for rule in &def.rules {
+        for nam in rule.body.refs() {
+          if *nam != def.name {
+            unused.remove(nam);
+          }
+        }
+      }
+    }
+    if let Some(main) = &self.book.entrypoint {
+      unused.remove(main);
+    }
+    unused
+  }
 }
 
 impl Term {
+  /// Whether this term has a [`Term::Err`] placeholder left by a previous error.
+  pub fn has_err(&self) -> bool {
+    maybe_grow(|| matches!(self, Term::Err) || self.children().any(Term::has_err))
+  }
+
   /// Checks that all variables are bound.
   /// Precondition: References have been resolved, implicit binds have been solved.
 
   pub fn check_unbound_vars<'a>(
     &'a mut self,
     scope: &mut HashMap<&'a Name, u64>,
+    def_names: &[Name],
     errs: &mut Vec<UnboundVarErr>,
   ) {
     let mut globals = HashMap::new();
-    check_uses(self, scope, &mut globals, errs);
+    check_uses(self, scope, def_names, &mut globals, errs);
 
     // Check global vars
     for (nam, (declared, used)) in globals.into_iter().filter(|(_, (d, u))| !(*d == 1 && *u == 1)) {
@@ -60,13 +130,15 @@ impl Term {
 pub fn check_uses<'a>(
   term: &'a mut Term,
   scope: &mut HashMap<&'a Name, u64>,
+  def_names: &[Name],
   globals: &mut HashMap<Name, (usize, usize)>,
   errs: &mut Vec<UnboundVarErr>,
 ) {
   maybe_grow(move || match term {
     Term::Var { nam } => {
       if !scope.contains_key(nam) {
-        errs.push(UnboundVarErr::Local(nam.clone()));
+        let suggestion = closest_name(nam, scope.keys().copied().chain(def_names));
+        errs.push(UnboundVarErr::Local { var: nam.clone(), suggestion, unused_def: false });
         *term = Term::Err;
       }
     }
@@ -76,13 +148,14 @@ pub fn check_uses<'a>(
 
     _ => {
       if let Some(pat) = term.pattern() {
-        check_global_binds(pat, globals)
+        check_global_binds(pat, globals);
+        check_linear_binds(pat, errs);
       }
       for (child, binds) in term.children_mut_with_binds() {
         for bind in binds.clone() {
           push_scope(bind.as_ref(), scope);
         }
-        check_uses(child, scope, globals, errs);
+        check_uses(child, scope, def_names, globals, errs);
         for bind in binds.rev() {
           pop_scope(bind.as_ref(), scope);
         }
@@ -104,6 +177,46 @@ pub fn check_global_binds(pat: &Pattern, globals: &mut HashMap<Name, (usize, usi
   }
 }
 
+/// Checks that a pattern doesn't bind the same variable more than once,
+/// since only the last of the repeated binds would be in scope.
+fn check_linear_binds(pat: &Pattern, errs: &mut Vec<UnboundVarErr>) {
+  let mut binds = HashSet::new();
+  for nam in pat.binds().flatten() {
+    if !binds.insert(nam) {
+      errs.push(UnboundVarErr::NonLinearPattern(nam.clone()));
+    }
+  }
+}
+
+/// The candidate most similar to `nam`, if it's close enough to likely be a typo.
+///
+/// Up to one edit is allowed for every three characters of `nam`, with at least one,
+/// but never as many as there are characters, which would be a different name altogether.
+fn closest_name<'a>(nam: &Name, candidates: impl Iterator<Item = &'a Name>) -> Option<Name> {
+  let len = nam.chars().count();
+  let max_distance = (len / 3).max(1);
+  candidates
+    .map(|candidate| (edit_distance(nam, candidate), candidate))
+    .filter(|(distance, _)| *distance <= max_distance && *distance < len)
+    .min()
+    .map(|(_, candidate)| candidate.clone())
+}
+
+/// The Levenshtein distance between two strings, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut prev = (0 ..= b.len()).collect::<Vec<_>>();
+  for (i, a_char) in a.chars().enumerate() {
+    let mut curr = vec![i + 1];
+    for (j, b_char) in b.iter().enumerate() {
+      let substitution = prev[j] + (a_char != *b_char) as usize;
+      curr.push(substitution.min(prev[j + 1] + 1).min(curr[j] + 1));
+    }
+    prev = curr;
+  }
+  prev[b.len()]
+}
+
 fn push_scope<'a>(nam: Option<&'a Name>, scope: &mut HashMap<&'a Name, u64>) {
   if let Some(nam) = nam {
     *scope.entry(nam).or_default() += 1;
@@ -123,7 +236,16 @@ fn pop_scope<'a>(nam: Option<&'a Name>, scope: &mut HashMap<&'a Name, u64>) {
 impl std::fmt::Display for UnboundVarErr {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      UnboundVarErr::Local(var) => write!(f, "Unbound variable '{var}'."),
+      UnboundVarErr::Local { var, suggestion: None, .. } => write!(f, "Unbound variable '{var}'."),
+      UnboundVarErr::Local { var, suggestion: Some(suggestion), unused_def: false } => {
+        write!(f, "Unbound variable '{var}'. Did you mean '{suggestion}'?")
+      }
+      UnboundVarErr::Local { var, suggestion: Some(suggestion), unused_def: true } => {
+        write!(
+          f,
+          "Unbound variable '{var}'. Did you mean to reference the definition '{suggestion}', which is otherwise unused?"
+        )
+      }
       UnboundVarErr::Global { var, declared, used } => match (declared, used) {
         (0, _) => write!(f, "Unbound unscoped variable '${var}'."),
         (_, 0) => write!(f, "Unscoped variable from lambda 'λ${var}' is never used."),
@@ -133,6 +255,39 @@ impl std::fmt::Display for UnboundVarErr {
           write!(f, "Unscoped lambda 'λ${var}' and unscoped variable '${var}' used more than once.")
         }
       },
+      UnboundVarErr::NonLinearPattern(var) => {
+        write!(f, "Variable '{var}' is bound more than once in the same pattern.")
+      }
+    }
+  }
+}
+
+#[test]
+fn unbound_vars_after_error() {
+  use crate::{
+    diagnostics::DiagnosticsConfig,
+    fun::{parser::TermParser, Book, Definition, Rule},
+  };
+
+  let mut book = Book::default();
+  for (name, body) in [("ok", "λx x"), ("broken", "λx (x y z)")] {
+    let mut body = TermParser::new(body).parse_term().unwrap();
+    if name == "broken" {
+      // Simulates a match arm that an earlier pass replaced after reporting an error.
+      let Term::Lam { bod, .. } = &mut body else { unreachable!() };
+      let Term::App { arg, .. } = bod.as_mut() else { unreachable!() };
+      **arg = Term::Err;
     }
+    let name = Name::new(name);
+    book.defs.insert(name.clone(), Definition {
+      name,
+      rules: vec![Rule { pats: vec![], body, span: None }],
+      builtin: false,
+    });
   }
+
+  let mut ctx = Ctx::new(&mut book, DiagnosticsConfig::default());
+  let err = ctx.check_unbound_vars().unwrap_err().to_string();
+  assert!(err.contains("A previous error prevented the analysis of this definition."));
+  assert!(!err.contains("Unbound variable"));
 }
@@ -0,0 +1,78 @@
+use crate::fun::{Book, Term};
+use std::{
+  fmt::Write,
+  hash::{DefaultHasher, Hash, Hasher},
+};
+
+impl Book {
+  /// A hash of the contents of the book, to tell if it changed.
+  ///
+  /// The order in which the definitions and datatypes were declared doesn't matter,
+  /// and neither do the names of the variables bound in each rule.
+  /// The order of the rules of a definition and of the constructors of a datatype does matter.
+  ///
+  /// Names are hashed by their text instead of by their interned id,
+  /// so the hash is the same between runs of the same compiler build.
+  pub fn content_hash(&self) -> u64 {
+    let mut out = String::new();
+
+    let mut adts = self.adts.iter().collect::<Vec<_>>();
+    adts.sort_by_key(|(name, _)| *name);
+    for (name, adt) in adts {
+      write!(out, "data {name} {}", adt.builtin).unwrap();
+      for (ctr, fields) in &adt.ctrs {
+        write!(out, " | {ctr}").unwrap();
+        for field in fields {
+          write!(out, " {}{}", if field.rec { "~" } else { "" }, field.nam).unwrap();
+        }
+      }
+      out.push('\n');
+    }
+
+    let mut ctrs = self.ctrs.iter().collect::<Vec<_>>();
+    ctrs.sort();
+    for (ctr, adt) in ctrs {
+      writeln!(out, "ctr {ctr} {adt}").unwrap();
+    }
+
+    let mut defs = self.defs.values().collect::<Vec<_>>();
+    defs.sort_by_key(|def| &def.name);
+    for def in defs {
+      writeln!(out, "def {} {}", def.name, def.builtin).unwrap();
+      for rule in &def.rules {
+        // Binding the patterns with lambdas renames their variables along with the ones in the body.
+        let mut term = rule.pats.iter().rfold(rule.body.clone(), |body, pat| Term::lam(pat.clone(), body));
+        term.make_var_names_unique();
+        writeln!(out, "{}", term.display_oneline()).unwrap();
+      }
+    }
+
+    if let Some(entrypoint) = &self.entrypoint {
+      writeln!(out, "entrypoint {entrypoint}").unwrap();
+    }
+
+    let mut hasher = DefaultHasher::new();
+    out.hash(&mut hasher);
+    hasher.finish()
+  }
+}
+
+#[test]
+fn content_hash() {
+  use crate::fun::parser::TermParser;
+
+  let hash = |code: &str| TermParser::new(code).parse_book(Book::default(), false).unwrap().content_hash();
+  let base = hash("data T = (A x) | B\nid = λx x\n(foo (T/A a) b) = (a b)\n(foo T/B b) = b\nmain = (id 1)");
+  let renamed =
+    hash("main = (id 1)\n(foo (T/A z) y) = (z y)\n(foo T/B y) = y\nid = λy y\ndata T = (A x) | B");
+  assert_eq!(base, renamed);
+
+  assert_ne!(
+    base,
+    hash("data T = (A x) | B\nid = λx x\n(foo (T/A a) b) = (b a)\n(foo T/B b) = b\nmain = (id 1)")
+  );
+  assert_ne!(
+    base,
+    hash("data T = B | (A x)\nid = λx x\n(foo (T/A a) b) = (a b)\n(foo T/B b) = b\nmain = (id 1)")
+  );
+}
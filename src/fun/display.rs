@@ -1,4 +1,4 @@
-use super::{Book, Definition, FanKind, Name, Num, Op, Pattern, Rule, Tag, Term};
+use super::{Book, Definition, FanKind, Name, Num, Op, Pattern, Rule, Span, Tag, Term};
 use crate::maybe_grow;
 use std::{fmt, ops::Deref};
 
@@ -42,108 +42,158 @@ macro_rules! display {
 
 impl fmt::Display for Term {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    maybe_grow(|| match self {
-      Term::Lam { tag, pat, bod } => {
-        write!(f, "{}λ{} {}", tag.display_padded(), pat, bod)
-      }
-      Term::Var { nam } => write!(f, "{nam}"),
-      Term::Link { nam } => write!(f, "${nam}"),
-      Term::Let { pat, val, nxt } => write!(f, "let {} = {}; {}", pat, val, nxt),
-      Term::Do { typ, bod } => write!(f, "do {typ} {{ {bod} }}"),
-      Term::Ask { pat, val, nxt } => write!(f, "ask {pat} = {val}; {nxt}"),
-      Term::Use { nam, val, nxt } => {
-        let Some(nam) = nam else { unreachable!() };
-        write!(f, "use {} = {}; {}", nam, val, nxt)
-      }
-      Term::Ref { nam: def_name } => write!(f, "{def_name}"),
-      Term::App { tag, fun, arg } => {
-        write!(f, "{}({} {})", tag.display_padded(), fun.display_app(tag), arg)
-      }
-      Term::Mat { arg, bnd, with, arms } => {
-        write!(f, "match ")?;
-        if let Some(bnd) = bnd {
-          write!(f, "{} = ", bnd)?;
+    self.display_with(false).fmt(f)
+  }
+}
+
+impl Term {
+  /// Displays the term in a single line, in a form that parses back to the same term.
+  ///
+  /// Unlike the [fmt::Display] implementation, floats are written with all their digits instead of rounded.
+  pub fn display_oneline(&self) -> String {
+    self.display_with(true).to_string()
+  }
+
+  fn display_with(&self, parseable: bool) -> impl fmt::Display + '_ {
+    DisplayFn(move |f| {
+      maybe_grow(|| match self {
+        Term::Lam { tag, pat, bod } => {
+          write!(f, "{}λ{} {}", tag.display_padded(), pat, bod.display_with(parseable))
         }
-        write!(f, "{} ", arg)?;
-        if !with.is_empty() {
-          write!(f, "with {} ", DisplayJoin(|| with, ", "))?;
+        Term::Var { nam } => write!(f, "{nam}"),
+        Term::Link { nam } => write!(f, "${nam}"),
+        Term::Let { pat, val, nxt } => {
+          write!(f, "let {} = {}; {}", pat, val.display_with(parseable), nxt.display_with(parseable))
         }
-        write!(f, "{{ ")?;
-        for arm in arms {
-          write!(f, "{}", var_as_str(&arm.0))?;
-          for var in &arm.1 {
-            write!(f, " {}", var_as_str(var))?;
-          }
-          write!(f, ": {}; ", arm.2)?;
+        Term::Do { typ, bod } => write!(f, "do {typ} {{ {} }}", bod.display_with(parseable)),
+        Term::Ask { pat, val, nxt } => {
+          write!(f, "ask {pat} = {}; {}", val.display_with(parseable), nxt.display_with(parseable))
         }
-        write!(f, "}}")
-      }
-      Term::Swt { arg, bnd, with, pred, arms } => {
-        write!(f, "switch ")?;
-        if let Some(bnd) = bnd {
-          write!(f, "{bnd} = ")?;
+        Term::Use { nam, val, nxt } => {
+          let Some(nam) = nam else { unreachable!() };
+          write!(f, "use {} = {}; {}", nam, val.display_with(parseable), nxt.display_with(parseable))
         }
-        write!(f, "{arg} ")?;
-        if !with.is_empty() {
-          write!(f, "with {} ", DisplayJoin(|| with, ", "))?;
+        Term::Ref { nam: def_name } => write!(f, "{def_name}"),
+        Term::App { tag, fun, arg } => {
+          write!(
+            f,
+            "{}({} {})",
+            tag.display_padded(),
+            fun.display_app(tag, parseable),
+            arg.display_with(parseable)
+          )
         }
-        write!(f, "{{ ")?;
-        for (i, arm) in arms.iter().enumerate() {
-          if i == arms.len() - 1 {
-            write!(f, "_")?;
-            if let Some(pred) = pred {
-              write!(f, " {pred}")?;
+        Term::Mat { arg, bnd, with, arms } => {
+          write!(f, "match ")?;
+          if let Some(bnd) = bnd {
+            write!(f, "{} = ", bnd)?;
+          }
+          write!(f, "{} ", arg.display_with(parseable))?;
+          if !with.is_empty() {
+            write!(f, "with {} ", DisplayJoin(|| with, ", "))?;
+          }
+          write!(f, "{{ ")?;
+          for arm in arms {
+            write!(f, "{}", var_as_str(&arm.0))?;
+            for var in &arm.1 {
+              write!(f, " {}", var_as_str(var))?;
+            }
+            if let Some((guard, body)) = arm.2.as_guarded() {
+              write!(f, " if {}: {}; ", guard.display_with(parseable), body.display_with(parseable))?;
+            } else {
+              write!(f, ": {}; ", arm.2.display_with(parseable))?;
             }
-          } else {
-            write!(f, "{i}")?;
           }
-          write!(f, ": {arm}; ")?;
-        }
-        write!(f, "}}")
-      }
-      Term::Fold { bnd, arg, with, arms } => {
-        write!(f, "fold ")?;
-        if let Some(bnd) = bnd {
-          write!(f, "{} = ", bnd)?;
+          write!(f, "}}")
         }
-        write!(f, "{} ", arg)?;
-        if !with.is_empty() {
-          write!(f, "with {} ", DisplayJoin(|| with, ", "))?;
+        Term::Swt { arg, bnd, with, pred, arms } => {
+          write!(f, "switch ")?;
+          if let Some(bnd) = bnd {
+            write!(f, "{bnd} = ")?;
+          }
+          write!(f, "{} ", arg.display_with(parseable))?;
+          if !with.is_empty() {
+            write!(f, "with {} ", DisplayJoin(|| with, ", "))?;
+          }
+          write!(f, "{{ ")?;
+          for (i, arm) in arms.iter().enumerate() {
+            if i == arms.len() - 1 {
+              write!(f, "_")?;
+              // The parser always names the predecessor after the switch's bind.
+              if let Some(pred) = pred
+                && !parseable
+              {
+                write!(f, " {pred}")?;
+              }
+            } else {
+              write!(f, "{i}")?;
+            }
+            write!(f, ": {}; ", arm.display_with(parseable))?;
+          }
+          write!(f, "}}")
         }
-        write!(f, "{{ ")?;
-        for arm in arms {
-          write!(f, "{}", var_as_str(&arm.0))?;
-          for var in &arm.1 {
-            write!(f, " {}", var_as_str(var))?;
+        Term::Fold { bnd, arg, with, arms } => {
+          write!(f, "fold ")?;
+          if let Some(bnd) = bnd {
+            write!(f, "{} = ", bnd)?;
+          }
+          write!(f, "{} ", arg.display_with(parseable))?;
+          if !with.is_empty() {
+            write!(f, "with {} ", DisplayJoin(|| with, ", "))?;
+          }
+          write!(f, "{{ ")?;
+          for arm in arms {
+            write!(f, "{}", var_as_str(&arm.0))?;
+            for var in &arm.1 {
+              write!(f, " {}", var_as_str(var))?;
+            }
+            if let Some((guard, body)) = arm.2.as_guarded() {
+              write!(f, " if {}: {}; ", guard.display_with(parseable), body.display_with(parseable))?;
+            } else {
+              write!(f, ": {}; ", arm.2.display_with(parseable))?;
+            }
           }
-          write!(f, ": {}; ", arm.2)?;
+          write!(f, "}}")
         }
-        write!(f, "}}")
-      }
-      Term::Bend { bind, init, cond, step, base } => {
-        write!(f, "bend ")?;
-        for (bind, init) in bind.iter().zip(init) {
-          if let Some(bind) = bind {
-            write!(f, "{} = ", bind)?;
+        Term::Bend { bind, init, cond, step, base } => {
+          write!(f, "bend ")?;
+          for (bind, init) in bind.iter().zip(init) {
+            if let Some(bind) = bind {
+              write!(f, "{} = ", bind)?;
+            }
+            write!(f, "{}, ", init.display_with(parseable))?;
           }
-          write!(f, "{}, ", init)?;
+          let sep = if parseable { "" } else { ";" };
+          write!(
+            f,
+            "{{ when {}: {}{sep} else: {} }}",
+            cond.display_with(parseable),
+            step.display_with(parseable),
+            base.display_with(parseable)
+          )
         }
-        write!(f, "{{ when {cond}: {step}; else: {base} }}")
-      }
-      Term::Fan { fan: FanKind::Tup, tag, els } => write!(f, "{}({})", tag, DisplayJoin(|| els.iter(), ", ")),
-      Term::Fan { fan: FanKind::Dup, tag, els } => write!(f, "{}{{{}}}", tag, DisplayJoin(|| els, " ")),
-      Term::Era => write!(f, "*"),
-      Term::Num { val: Num::U24(val) } => write!(f, "{val}"),
-      Term::Num { val: Num::I24(val) } => write!(f, "{}{}", if *val < 0 { "-" } else { "+" }, val.abs()),
-      Term::Num { val: Num::F24(val) } => write!(f, "{val:.3}"),
-      Term::Nat { val } => write!(f, "#{val}"),
-      Term::Str { val } => write!(f, "{val:?}"),
-      Term::Oper { opr, fst, snd } => {
-        write!(f, "({} {} {})", opr, fst, snd)
-      }
-      Term::List { els } => write!(f, "[{}]", DisplayJoin(|| els.iter(), ", "),),
-      Term::Open { typ, var, bod } => write!(f, "open {typ} {var}; {bod}"),
-      Term::Err => write!(f, "<Invalid>"),
+        Term::Fan { fan: FanKind::Tup, tag, els } => {
+          write!(f, "{}({})", tag, DisplayJoin(|| els.iter().map(|el| el.display_with(parseable)), ", "))
+        }
+        Term::Fan { fan: FanKind::Dup, tag, els } => {
+          write!(f, "{}{{{}}}", tag, DisplayJoin(|| els.iter().map(|el| el.display_with(parseable)), " "))
+        }
+        Term::Era => write!(f, "*"),
+        Term::Num { val: Num::U24(val) } => write!(f, "{val}"),
+        Term::Num { val: Num::I24(val) } => write!(f, "{}{}", if *val < 0 { "-" } else { "+" }, val.abs()),
+        Term::Num { val: Num::F24(val) } if parseable => write!(f, "{val:?}"),
+        Term::Num { val: Num::F24(val) } => write!(f, "{val:.3}"),
+        Term::Nat { val } => write!(f, "#{val}"),
+        Term::Str { val } => write!(f, "{val:?}"),
+        Term::Oper { opr, fst, snd } => {
+          write!(f, "({} {} {})", opr, fst.display_with(parseable), snd.display_with(parseable))
+        }
+        Term::List { els } => {
+          write!(f, "[{}]", DisplayJoin(|| els.iter().map(|el| el.display_with(parseable)), ", "))
+        }
+        Term::Open { typ, var, bod } => write!(f, "open {typ} {var}; {}", bod.display_with(parseable)),
+        Term::Err => write!(f, "<Invalid>"),
+      })
     })
   }
 }
@@ -200,6 +250,12 @@ impl fmt::Display for Book {
   }
 }
 
+impl fmt::Display for Span {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {}, column {}", self.line, self.column)
+  }
+}
+
 impl fmt::Display for Name {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     self.0.fmt(f)
@@ -207,13 +263,13 @@ impl fmt::Display for Name {
 }
 
 impl Term {
-  fn display_app<'a>(&'a self, tag: &'a Tag) -> impl fmt::Display + 'a {
+  fn display_app<'a>(&'a self, tag: &'a Tag, parseable: bool) -> impl fmt::Display + 'a {
     maybe_grow(|| {
       DisplayFn(move |f| match self {
         Term::App { tag: tag2, fun, arg } if tag2 == tag => {
-          write!(f, "{} {}", fun.display_app(tag), arg)
+          write!(f, "{} {}", fun.display_app(tag, parseable), arg.display_with(parseable))
         }
-        _ => write!(f, "{}", self),
+        _ => write!(f, "{}", self.display_with(parseable)),
       })
     })
   }
@@ -352,7 +408,11 @@ impl Term {
             for var in &arm.1 {
               write!(f, " {}", var_as_str(var))?;
             }
-            write!(f, ": {}; ", arm.2.display_pretty(tab + 4))?;
+            if let Some((guard, body)) = arm.2.as_guarded() {
+              write!(f, " if {}: {}; ", guard.display_pretty(tab + 4), body.display_pretty(tab + 4))?;
+            } else {
+              write!(f, ": {}; ", arm.2.display_pretty(tab + 4))?;
+            }
           }
           write!(f, "\n{:tab$}}}", "")
         }
@@ -394,7 +454,11 @@ impl Term {
             for var in &arm.1 {
               write!(f, " {}", var_as_str(var))?;
             }
-            write!(f, ": {}; ", arm.2.display_pretty(tab + 4))?;
+            if let Some((guard, body)) = arm.2.as_guarded() {
+              write!(f, " if {}: {}; ", guard.display_pretty(tab + 4), body.display_pretty(tab + 4))?;
+            } else {
+              write!(f, ": {}; ", arm.2.display_pretty(tab + 4))?;
+            }
           }
           write!(f, "\n{:tab$}}}", "")
         }
@@ -439,3 +503,23 @@ impl Term {
     })
   }
 }
+
+#[test]
+fn display_oneline_round_trip() {
+  use super::parser::TermParser;
+
+  let terms = [
+    "λa λb (a b (λc c) let d = (+ a 1); d)",
+    "let (a, b) = (1, -2); let {c d} = a; [c, d, \"x\\ny\", 0.1234567, +5]",
+    "match x = (f y) with z { Maybe/Some: (z x.val); Maybe/None: switch z { 0: 1.5; _: z-1 } }",
+    "bend x = 0, { when (< x 3): (fork (+ x 1)) else: x }",
+    "use a = 2; fold x { List/Cons: (+ x.head x.tail); List/Nil: 0 }",
+    "match x { Maybe/Some if (> x.val 1): x.val; y: 0 }",
+  ];
+  for code in terms {
+    let term = TermParser::new(code).parse_term().unwrap();
+    let line = term.display_oneline();
+    assert!(!line.contains('\n'));
+    assert_eq!(TermParser::new(&line).parse_term().unwrap(), term, "{line}");
+  }
+}
@@ -0,0 +1,387 @@
+use crate::{
+  diagnostics::Diagnostics,
+  fun::{Book, FanKind, Name, Num, Op, Pattern, Tag, Term},
+  maybe_grow,
+};
+use std::rc::Rc;
+
+/// Reduces a term of a desugared book to normal form by walking it, without compiling it to a net.
+///
+/// It's much slower than running the compiled book with hvm, but it's simpler to follow
+/// and can be used as a reference to check the results of the runtime.
+///
+/// Like in hvm, references are only expanded when their value is needed or when reading back the result,
+/// duplications copy their value and operations on superpositions are distributed over its elements.
+/// Unscoped variables and interactions between lambdas and applications with different tags are not supported.
+///
+/// The result is in the same form as the one read back from a net,
+/// so it still has to be resugared to look like the result of [crate::run_book].
+pub fn eval_term(book: &Book, term: &Term) -> Result<Term, Diagnostics> {
+  let mut eval = Eval { book, fresh: 0, lets: vec![] };
+  let mut term = eval.eval_quoted(|eval| match term {
+    // Like when hvm starts running a book, only the entrypoint reference itself is expanded.
+    Term::Ref { nam } => eval.expand(nam),
+    _ => eval.eval(term, &Env::default()),
+  })?;
+  term.make_var_names_unique();
+  Ok(term)
+}
+
+struct Eval<'a> {
+  book: &'a Book,
+  /// The counter for the names of the variables bound in the result.
+  fresh: usize,
+  /// The tuples of unknown values destructured in the term being quoted, to bind around it.
+  lets: Vec<(Pattern, Term)>,
+}
+
+#[derive(Clone)]
+enum Value<'a> {
+  Lam {
+    tag: Tag,
+    pat: &'a Pattern,
+    bod: &'a Term,
+    env: Env<'a>,
+  },
+  Fan {
+    fan: FanKind,
+    tag: Tag,
+    els: Vec<Value<'a>>,
+  },
+  Num(Num),
+  Ref(Name),
+  Era,
+  /// A term that can't be reduced further, because it depends on a variable bound in the result.
+  Stuck(Term),
+}
+
+/// The values of the variables in scope, most recently bound first.
+#[derive(Clone, Default)]
+struct Env<'a>(Option<Rc<(Name, Value<'a>, Env<'a>)>>);
+
+impl<'a> Env<'a> {
+  fn bind(&self, nam: Name, val: Value<'a>) -> Self {
+    Env(Some(Rc::new((nam, val, self.clone()))))
+  }
+
+  fn get(&self, nam: &Name) -> Option<&Value<'a>> {
+    let mut env = self;
+    while let Some(node) = &env.0 {
+      if &node.0 == nam {
+        return Some(&node.1);
+      }
+      env = &node.2;
+    }
+    None
+  }
+}
+
+impl<'a> Eval<'a> {
+  fn eval(&mut self, term: &'a Term, env: &Env<'a>) -> Result<Value<'a>, String> {
+    maybe_grow(|| match term {
+      Term::Var { nam } => env.get(nam).cloned().ok_or_else(|| format!("Unbound variable '{nam}'.")),
+      Term::Lam { tag, pat, bod } => Ok(Value::Lam { tag: tag.clone(), pat, bod, env: env.clone() }),
+      Term::Let { pat, val, nxt } => {
+        let val = self.eval(val, env)?;
+        let env = self.bind(pat, val, env.clone())?;
+        self.eval(nxt, &env)
+      }
+      Term::App { tag, fun, arg } => {
+        let fun = self.eval(fun, env)?;
+        let arg = self.eval(arg, env)?;
+        self.apply(fun, tag, arg)
+      }
+      Term::Fan { fan, tag, els } => {
+        let els = els.iter().map(|el| self.eval(el, env)).collect::<Result<_, _>>()?;
+        Ok(Value::Fan { fan: *fan, tag: tag.clone(), els })
+      }
+      Term::Num { val } => Ok(Value::Num(*val)),
+      Term::Oper { opr, fst, snd } => {
+        let fst = self.eval(fst, env)?;
+        let snd = self.eval(snd, env)?;
+        self.operate(*opr, fst, snd)
+      }
+      Term::Swt { arg, bnd: _, with, pred, arms } if with.is_empty() => {
+        let arg = self.eval(arg, env)?;
+        self.switch(arg, pred, arms, env)
+      }
+      Term::Ref { nam } => Ok(Value::Ref(nam.clone())),
+      Term::Era => Ok(Value::Era),
+      Term::Link { .. } => Err("Unscoped variables are not supported by the interpreter.".to_string()),
+      Term::Err => Err("Reached an invalid term.".to_string()),
+      _ => Err(format!("Expected a desugared term, found '{}'.", term.display_oneline())),
+    })
+  }
+
+  /// Expands the references until reaching a value that's not a reference.
+  fn whnf(&mut self, mut val: Value<'a>) -> Result<Value<'a>, String> {
+    while let Value::Ref(nam) = &val {
+      val = self.expand(&nam.clone())?;
+    }
+    Ok(val)
+  }
+
+  fn expand(&mut self, nam: &Name) -> Result<Value<'a>, String> {
+    let Some(def) = self.book.defs.get(nam) else {
+      return Err(format!("Reference to unknown definition '{nam}'."));
+    };
+    let [rule] = def.rules.as_slice() else {
+      return Err(format!("Expected the definition '{nam}' to be desugared into a single rule."));
+    };
+    self.eval(&rule.body, &Env::default())
+  }
+
+  fn apply(&mut self, fun: Value<'a>, tag: &Tag, arg: Value<'a>) -> Result<Value<'a>, String> {
+    maybe_grow(|| match self.whnf(fun)? {
+      Value::Lam { tag: lam_tag, pat, bod, env } if lam_tag == *tag => {
+        let env = self.bind(pat, arg, env)?;
+        self.eval(bod, &env)
+      }
+      Value::Lam { .. } => {
+        Err("Applications and lambdas with different tags are not supported by the interpreter.".to_string())
+      }
+      Value::Fan { fan: FanKind::Dup, tag: sup_tag, els } => {
+        let els = els.into_iter().map(|el| self.apply(el, tag, arg.clone())).collect::<Result<_, _>>()?;
+        Ok(Value::Fan { fan: FanKind::Dup, tag: sup_tag, els })
+      }
+      // Like in hvm, the argument is erased.
+      val @ (Value::Era | Value::Num(_)) => Ok(val),
+      Value::Stuck(fun) => {
+        let arg = self.quote(arg)?;
+        Ok(Value::Stuck(Term::App { tag: tag.clone(), fun: Box::new(fun), arg: Box::new(arg) }))
+      }
+      Value::Fan { fan: FanKind::Tup, .. } => Err("Applied a tuple as a function.".to_string()),
+      Value::Ref(_) => unreachable!(),
+    })
+  }
+
+  fn switch(
+    &mut self,
+    arg: Value<'a>,
+    pred: &Option<Name>,
+    arms: &'a [Term],
+    env: &Env<'a>,
+  ) -> Result<Value<'a>, String> {
+    maybe_grow(|| match self.whnf(arg)? {
+      Value::Num(Num::U24(n)) => {
+        let n = n as usize;
+        let succ = arms.len() - 1;
+        if n < succ {
+          return self.eval(&arms[n], env);
+        }
+        let pred_val = Value::Num(Num::U24((n - succ) as u32));
+        match pred {
+          Some(pred) => self.eval(&arms[succ], &env.bind(pred.clone(), pred_val)),
+          None => {
+            let arm = self.eval(&arms[succ], env)?;
+            self.apply(arm, &Tag::Static, pred_val)
+          }
+        }
+      }
+      Value::Fan { fan: FanKind::Dup, tag, els } => {
+        let els = els.into_iter().map(|el| self.switch(el, pred, arms, env)).collect::<Result<_, _>>()?;
+        Ok(Value::Fan { fan: FanKind::Dup, tag, els })
+      }
+      Value::Era => Ok(Value::Era),
+      Value::Stuck(arg) => {
+        let mut quoted = vec![];
+        for (i, arm) in arms.iter().enumerate() {
+          let (pred, env) = match pred {
+            Some(pred) if i == arms.len() - 1 => {
+              let var = self.fresh_name();
+              (Some(var.clone()), env.bind(pred.clone(), Value::Stuck(Term::Var { nam: var })))
+            }
+            _ => (None, env.clone()),
+          };
+          quoted.push((pred, self.eval_quoted(|eval| eval.eval(arm, &env))?));
+        }
+        let pred = quoted.last().and_then(|(pred, _)| pred.clone());
+        let arms = quoted.into_iter().map(|(_, arm)| arm).collect();
+        Ok(Value::Stuck(Term::Swt { arg: Box::new(arg), bnd: None, with: vec![], pred, arms }))
+      }
+      _ => Err("Expected a u24 number as the argument of a switch.".to_string()),
+    })
+  }
+
+  fn operate(&mut self, opr: Op, fst: Value<'a>, snd: Value<'a>) -> Result<Value<'a>, String> {
+    maybe_grow(|| match (self.whnf(fst)?, self.whnf(snd)?) {
+      (Value::Num(fst), Value::Num(snd)) => Ok(Value::Num(operate_nums(opr, fst, snd)?)),
+      (Value::Fan { fan: FanKind::Dup, tag, els }, snd) => {
+        let els = els.into_iter().map(|el| self.operate(opr, el, snd.clone())).collect::<Result<_, _>>()?;
+        Ok(Value::Fan { fan: FanKind::Dup, tag, els })
+      }
+      (fst, Value::Fan { fan: FanKind::Dup, tag, els }) => {
+        let els = els.into_iter().map(|el| self.operate(opr, fst.clone(), el)).collect::<Result<_, _>>()?;
+        Ok(Value::Fan { fan: FanKind::Dup, tag, els })
+      }
+      (Value::Era, _) | (_, Value::Era) => Ok(Value::Era),
+      (fst @ (Value::Stuck(_) | Value::Num(_)), snd @ (Value::Stuck(_) | Value::Num(_))) => {
+        let fst = Box::new(self.quote(fst)?);
+        let snd = Box::new(self.quote(snd)?);
+        Ok(Value::Stuck(Term::Oper { opr, fst, snd }))
+      }
+      _ => Err(format!("Expected numbers as the arguments of the operation '{opr}'.")),
+    })
+  }
+
+  /// Binds the variables of a pattern to the parts of a value.
+  fn bind(&mut self, pat: &Pattern, val: Value<'a>, env: Env<'a>) -> Result<Env<'a>, String> {
+    match pat {
+      Pattern::Var(None) => Ok(env),
+      Pattern::Var(Some(nam)) => Ok(env.bind(nam.clone(), val)),
+      Pattern::Fan(fan, tag, pats) => {
+        let vals = self.project(val, *fan, tag, pats.len())?;
+        pats.iter().zip(vals).try_fold(env, |env, (pat, val)| self.bind(pat, val, env))
+      }
+      Pattern::Chn(_) => Err("Unscoped variables are not supported by the interpreter.".to_string()),
+      _ => Err(format!("Expected a desugared pattern, found '{pat}'.")),
+    }
+  }
+
+  /// Splits a value into `len` parts, for a tuple or duplication pattern.
+  fn project(
+    &mut self,
+    val: Value<'a>,
+    fan: FanKind,
+    tag: &Tag,
+    len: usize,
+  ) -> Result<Vec<Value<'a>>, String> {
+    maybe_grow(|| match self.whnf(val)? {
+      Value::Fan { fan: val_fan, tag: val_tag, els }
+        if val_fan == fan && val_tag == *tag && els.len() == len =>
+      {
+        Ok(els)
+      }
+      // Projecting each element of a superposition, and superposing the results.
+      Value::Fan { fan: FanKind::Dup, tag: sup_tag, els } => {
+        let mut parts = vec![vec![]; len];
+        for el in els {
+          for (part, el) in parts.iter_mut().zip(self.project(el, fan, tag, len)?) {
+            part.push(el);
+          }
+        }
+        Ok(parts.into_iter().map(|els| Value::Fan { fan: FanKind::Dup, tag: sup_tag.clone(), els }).collect())
+      }
+      val @ (Value::Era | Value::Num(_)) => Ok(vec![val; len]),
+      val if fan == FanKind::Dup => Ok(vec![val; len]),
+      Value::Stuck(term) => {
+        let vars = (0 .. len).map(|_| self.fresh_name()).collect::<Vec<_>>();
+        let pats = vars.iter().map(|var| Pattern::Var(Some(var.clone()))).collect();
+        self.lets.push((Pattern::Fan(fan, tag.clone(), pats), term));
+        Ok(vars.into_iter().map(|nam| Value::Stuck(Term::Var { nam })).collect())
+      }
+      _ => Err(format!("Expected a {len}-tuple.")),
+    })
+  }
+
+  /// Converts a value back to a term, reducing the bodies of the lambdas and expanding the references.
+  fn quote(&mut self, val: Value<'a>) -> Result<Term, String> {
+    maybe_grow(|| match val {
+      // Constructors are kept as references, so that they can be resugared.
+      Value::Ref(nam) if self.book.ctrs.contains_key(&nam) => Ok(Term::Ref { nam }),
+      Value::Ref(nam) => {
+        let val = self.expand(&nam)?;
+        self.quote(val)
+      }
+      Value::Lam { tag, pat, bod, env } => {
+        let mut new_pat = pat.clone();
+        let mut env = env;
+        for nam in new_pat.binds_mut().flatten() {
+          let var = self.fresh_name();
+          env = env.bind(std::mem::replace(nam, var.clone()), Value::Stuck(Term::Var { nam: var }));
+        }
+        let bod = self.eval_quoted(|eval| eval.eval(bod, &env))?;
+        Ok(Term::Lam { tag, pat: Box::new(new_pat), bod: Box::new(bod) })
+      }
+      // In a net, fans have two elements, so the bigger ones are read back as nested pairs.
+      Value::Fan { fan, tag, els } => {
+        let mut els = els.into_iter().map(|el| self.quote(el)).collect::<Result<Vec<_>, _>>()?;
+        let mut term = els.pop().unwrap_or(Term::Era);
+        while let Some(el) = els.pop() {
+          term = Term::Fan { fan, tag: tag.clone(), els: vec![el, term] };
+        }
+        Ok(term)
+      }
+      Value::Num(val) => Ok(Term::Num { val }),
+      Value::Era => Ok(Term::Era),
+      Value::Stuck(term) => Ok(term),
+    })
+  }
+
+  /// Evaluates and quotes a term in a new scope,
+  /// binding the tuples of unknown values destructured in it around the result.
+  fn eval_quoted(
+    &mut self,
+    eval: impl FnOnce(&mut Self) -> Result<Value<'a>, String>,
+  ) -> Result<Term, String> {
+    let start = self.lets.len();
+    let val = eval(self)?;
+    let mut term = self.quote(val)?;
+    for (pat, val) in self.lets.drain(start ..).rev() {
+      term = Term::Let { pat: Box::new(pat), val: Box::new(val), nxt: Box::new(term) };
+    }
+    Ok(term)
+  }
+
+  fn fresh_name(&mut self) -> Name {
+    self.fresh += 1;
+    Name::new(format!("%x{}", self.fresh))
+  }
+}
+
+pub fn operate_nums(opr: Op, fst: Num, snd: Num) -> Result<Num, String> {
+  let bool = |b: bool| Num::U24(b as u32);
+  let num = match (fst, snd) {
+    (Num::U24(a), Num::U24(b)) => match opr {
+      Op::ADD => Num::U24(a.wrapping_add(b) & 0xFFFFFF),
+      Op::SUB => Num::U24(a.wrapping_sub(b) & 0xFFFFFF),
+      Op::MUL => Num::U24(a.wrapping_mul(b) & 0xFFFFFF),
+      Op::DIV => Num::U24(a.checked_div(b).ok_or("Division by zero.")?),
+      Op::REM => Num::U24(a.checked_rem(b).ok_or("Division by zero.")?),
+      Op::EQL => bool(a == b),
+      Op::NEQ => bool(a != b),
+      Op::LTN => bool(a < b),
+      Op::GTN => bool(a > b),
+      Op::AND | Op::ATN => Num::U24(a & b),
+      Op::OR | Op::LOG => Num::U24(a | b),
+      Op::XOR | Op::POW => Num::U24(a ^ b),
+    },
+    (Num::I24(a), Num::I24(b)) => {
+      let i24 = |n: i32| Num::I24(n << 8 >> 8);
+      match opr {
+        Op::ADD => i24(a.wrapping_add(b)),
+        Op::SUB => i24(a.wrapping_sub(b)),
+        Op::MUL => i24(a.wrapping_mul(b)),
+        Op::DIV => i24(a.checked_div(b).ok_or("Division by zero.")?),
+        Op::REM => i24(a.checked_rem(b).ok_or("Division by zero.")?),
+        Op::EQL => bool(a == b),
+        Op::NEQ => bool(a != b),
+        Op::LTN => bool(a < b),
+        Op::GTN => bool(a > b),
+        Op::AND | Op::ATN => i24(a & b),
+        Op::OR | Op::LOG => i24(a | b),
+        Op::XOR | Op::POW => i24(a ^ b),
+      }
+    }
+    (Num::F24(a), Num::F24(b)) => {
+      // Only the first 16 bits of the mantissa are kept in an f24.
+      let f24 = |n: f32| Num::F24(f32::from_bits(n.to_bits() & !0x7F));
+      match opr {
+        Op::ADD => f24(a + b),
+        Op::SUB => f24(a - b),
+        Op::MUL => f24(a * b),
+        Op::DIV => f24(a / b),
+        Op::REM => f24(a % b),
+        Op::EQL => bool(a == b),
+        Op::NEQ => bool(a != b),
+        Op::LTN => bool(a < b),
+        Op::GTN => bool(a > b),
+        Op::ATN | Op::AND => f24(a.atan2(b)),
+        Op::LOG | Op::OR => f24(b.log(a)),
+        Op::POW | Op::XOR => f24(a.powf(b)),
+      }
+    }
+    _ => return Err(format!("Operation '{opr}' on numbers of different types.")),
+  };
+  Ok(num)
+}
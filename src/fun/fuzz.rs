@@ -0,0 +1,301 @@
+//! Generation of random well-scoped books, to test that the compiler handles any valid program.
+//!
+//! The choices of the generator are read from a sequence of bytes, like the input of a fuzzer,
+//! so that a failing input can be saved and replayed.
+//! When the bytes run out every choice is the first one, which always ends the generation.
+//!
+//! The generated books are simply typed and don't have recursion, so they always terminate:
+//! each definition takes numbers or ADT values and returns a number,
+//! and only calls the definitions generated before it.
+
+use crate::fun::{parser::TermParser, Book};
+use std::fmt::Write;
+
+/// The maximum depth of the generated terms.
+const MAX_DEPTH: usize = 6;
+
+/// A source of choices for the generator.
+pub struct Unstructured<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Unstructured<'a> {
+  pub fn new(bytes: &'a [u8]) -> Self {
+    Self { bytes }
+  }
+
+  /// Chooses a number in `0 .. n`, or `0` if the bytes ran out.
+  fn choose(&mut self, n: usize) -> usize {
+    match self.bytes.split_first() {
+      Some((byte, rest)) => {
+        self.bytes = rest;
+        *byte as usize % n
+      }
+      None => 0,
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Type {
+  Num,
+  /// An ADT, by its index.
+  Adt(usize),
+}
+
+struct Ctr {
+  fields: Vec<Type>,
+}
+
+struct Def {
+  params: Vec<Type>,
+}
+
+struct Gen<'u, 'a> {
+  u: &'u mut Unstructured<'a>,
+  adts: Vec<Vec<Ctr>>,
+  defs: Vec<Def>,
+  fresh: usize,
+}
+
+/// Generates the source code of a random book, with a `main` that returns a number.
+pub fn arbitrary_book_code(u: &mut Unstructured) -> String {
+  let mut gen = Gen { u, adts: vec![], defs: vec![], fresh: 0 };
+  let mut code = String::new();
+
+  for adt in 0 .. gen.u.choose(3) {
+    // The first constructor only has numbers, so that a value of the ADT can always be built.
+    let mut ctrs = vec![Ctr { fields: vec![Type::Num; gen.u.choose(3)] }];
+    for _ in 0 .. gen.u.choose(3) {
+      let fields = (0 .. gen.u.choose(3))
+        .map(|_| if gen.u.choose(2) == 0 { Type::Num } else { Type::Adt(adt) })
+        .collect();
+      ctrs.push(Ctr { fields });
+    }
+    write!(code, "data T{adt} =").unwrap();
+    for (i, ctr) in ctrs.iter().enumerate() {
+      let sep = if i == 0 { "" } else { " |" };
+      if ctr.fields.is_empty() {
+        write!(code, "{sep} C{i}").unwrap();
+      } else {
+        write!(code, "{sep} (C{i}").unwrap();
+        for field in 0 .. ctr.fields.len() {
+          write!(code, " f{field}").unwrap();
+        }
+        code.push(')');
+      }
+    }
+    code.push('\n');
+    gen.adts.push(ctrs);
+  }
+
+  for def in 0 .. gen.u.choose(4) + 1 {
+    let params = (0 .. gen.u.choose(3)).map(|_| gen.typ()).collect::<Vec<_>>();
+    let mut scope = vec![];
+    write!(code, "\nd{def} =").unwrap();
+    for typ in &params {
+      let nam = gen.fresh_name();
+      write!(code, " λ{nam}").unwrap();
+      scope.push((nam, *typ));
+    }
+    let body = gen.term(Type::Num, &mut scope, 0);
+    writeln!(code, " {body}").unwrap();
+    gen.defs.push(Def { params });
+  }
+
+  let body = gen.term(Type::Num, &mut vec![], 0);
+  writeln!(code, "\nmain = {body}").unwrap();
+  code
+}
+
+/// Generates a random book, see [arbitrary_book_code].
+pub fn arbitrary_book(u: &mut Unstructured) -> Book {
+  let code = arbitrary_book_code(u);
+  match TermParser::new(&code).parse_book(Book::builtins(), false) {
+    Ok(book) => book,
+    Err(e) => panic!("Generated an invalid book:\n{code}\n{e}"),
+  }
+}
+
+impl Gen<'_, '_> {
+  fn typ(&mut self) -> Type {
+    match self.u.choose(self.adts.len() + 1) {
+      0 => Type::Num,
+      adt => Type::Adt(adt - 1),
+    }
+  }
+
+  fn fresh_name(&mut self) -> String {
+    self.fresh += 1;
+    format!("v{}", self.fresh)
+  }
+
+  /// Generates a term of type `typ` using only the variables in `scope`.
+  fn term(&mut self, typ: Type, scope: &mut Vec<(String, Type)>, depth: usize) -> String {
+    let vars =
+      scope.iter().filter(|(_, var_typ)| *var_typ == typ).map(|(nam, _)| nam.clone()).collect::<Vec<_>>();
+    if depth >= MAX_DEPTH {
+      return self.leaf(typ, &vars);
+    }
+    match typ {
+      Type::Num => self.num_term(scope, depth),
+      Type::Adt(adt) => match self.u.choose(2) {
+        0 => self.leaf(typ, &vars),
+        _ => {
+          let ctr = self.u.choose(self.adts[adt].len());
+          self.ctr(adt, ctr, scope, depth)
+        }
+      },
+    }
+  }
+
+  /// Generates a term without subterms, or the simplest value of an ADT.
+  fn leaf(&mut self, typ: Type, vars: &[String]) -> String {
+    match (self.u.choose(vars.len() + 1), typ) {
+      (0, Type::Num) => self.u.choose(256).to_string(),
+      (0, Type::Adt(adt)) => self.ctr(adt, 0, &mut vec![], MAX_DEPTH),
+      (var, _) => vars[var - 1].clone(),
+    }
+  }
+
+  fn ctr(&mut self, adt: usize, ctr: usize, scope: &mut Vec<(String, Type)>, depth: usize) -> String {
+    let fields = self.adts[adt][ctr].fields.clone();
+    let mut term = format!("(T{adt}/C{ctr}");
+    for field in fields {
+      let field = self.term(field, scope, depth + 1);
+      write!(term, " {field}").unwrap();
+    }
+    term.push(')');
+    term
+  }
+
+  fn num_term(&mut self, scope: &mut Vec<(String, Type)>, depth: usize) -> String {
+    let depth = depth + 1;
+    match self.u.choose(8) {
+      0 | 1 => {
+        let vars =
+          scope.iter().filter(|(_, typ)| *typ == Type::Num).map(|(nam, _)| nam.clone()).collect::<Vec<_>>();
+        self.leaf(Type::Num, &vars)
+      }
+      2 => {
+        let opr = ["+", "-", "*", "<", "=="][self.u.choose(5)];
+        let fst = self.term(Type::Num, scope, depth);
+        let snd = self.term(Type::Num, scope, depth);
+        format!("({opr} {fst} {snd})")
+      }
+      3 if !self.defs.is_empty() => {
+        let def = self.u.choose(self.defs.len());
+        let mut call = format!("(d{def}");
+        for param in self.defs[def].params.clone() {
+          let arg = self.term(param, scope, depth);
+          write!(call, " {arg}").unwrap();
+        }
+        call.push(')');
+        call
+      }
+      4 => {
+        let typ = self.typ();
+        let val = self.term(typ, scope, depth);
+        let nam = self.fresh_name();
+        scope.push((nam.clone(), typ));
+        let nxt = self.term(Type::Num, scope, depth);
+        scope.pop();
+        format!("let {nam} = {val}; {nxt}")
+      }
+      5 => {
+        let fst = self.term(Type::Num, scope, depth);
+        let snd = self.term(Type::Num, scope, depth);
+        let (a, b) = (self.fresh_name(), self.fresh_name());
+        scope.extend([(a.clone(), Type::Num), (b.clone(), Type::Num)]);
+        let nxt = self.term(Type::Num, scope, depth);
+        scope.truncate(scope.len() - 2);
+        format!("let ({a}, {b}) = ({fst}, {snd}); {nxt}")
+      }
+      6 => {
+        let arg = self.term(Type::Num, scope, depth);
+        let zero = self.term(Type::Num, scope, depth);
+        let nam = self.fresh_name();
+        scope.push((format!("{nam}-1"), Type::Num));
+        let succ = self.term(Type::Num, scope, depth);
+        scope.pop();
+        format!("switch {nam} = {arg} {{ 0: {zero}; _: {succ} }}")
+      }
+      _ if !self.adts.is_empty() => {
+        let adt = self.u.choose(self.adts.len());
+        let arg = self.term(Type::Adt(adt), scope, depth);
+        let nam = self.fresh_name();
+        let mut arms = String::new();
+        for ctr in 0 .. self.adts[adt].len() {
+          let fields = self.adts[adt][ctr].fields.clone();
+          let len = scope.len();
+          scope.extend(fields.iter().enumerate().map(|(i, typ)| (format!("{nam}.f{i}"), *typ)));
+          let body = self.term(Type::Num, scope, depth);
+          scope.truncate(len);
+          write!(arms, " T{adt}/C{ctr}: {body};").unwrap();
+        }
+        format!("match {nam} = {arg} {{{arms} }}")
+      }
+      _ => self.u.choose(256).to_string(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    compile_book, desugar_book,
+    diagnostics::{DiagnosticsConfig, Severity},
+    fun::{eval::eval_term, Num, Term},
+    CompileHooks, CompileOpts,
+  };
+
+  /// Deterministic pseudo-random inputs for the generator.
+  fn inputs() -> impl Iterator<Item = Vec<u8>> {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    (0 .. 300).map(move |_| {
+      (0 .. 256)
+        .map(|_| {
+          state ^= state << 13;
+          state ^= state >> 7;
+          state ^= state << 17;
+          state as u8
+        })
+        .collect()
+    })
+  }
+
+  fn diagnostics_cfg() -> DiagnosticsConfig {
+    DiagnosticsConfig::new(Severity::Allow, false)
+  }
+
+  #[test]
+  fn compile_arbitrary_books() {
+    for bytes in inputs() {
+      // The size limit of hvm is a limitation of the runtime, not an error in the compiler.
+      let opts = CompileOpts { check_net_size: false, ..CompileOpts::default() };
+      for opts in [opts.clone(), opts.set_all()] {
+        let code = arbitrary_book_code(&mut Unstructured::new(&bytes));
+        let mut book = arbitrary_book(&mut Unstructured::new(&bytes));
+        if let Err(e) = compile_book(&mut book, opts, diagnostics_cfg(), None, CompileHooks::default()) {
+          panic!("Failed to compile a generated book:\n{code}\n{e}");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn eval_arbitrary_books() {
+    for bytes in inputs() {
+      let code = arbitrary_book_code(&mut Unstructured::new(&bytes));
+      let mut book = arbitrary_book(&mut Unstructured::new(&bytes));
+      desugar_book(&mut book, CompileOpts::default(), diagnostics_cfg(), None).unwrap();
+      let main = Term::r#ref(book.entrypoint.as_ref().unwrap());
+      match eval_term(&book, &main) {
+        Ok(Term::Num { val: Num::U24(_) }) => {}
+        Ok(term) => panic!("Expected a generated book to return a number:\n{code}\nReturned: {term}"),
+        Err(e) => panic!("Failed to evaluate a generated book:\n{code}\n{e}"),
+      }
+    }
+  }
+}
@@ -0,0 +1,185 @@
+//! Reduction of a failing book to a smaller one that fails in the same way,
+//! to find a minimal program that reproduces a compiler bug.
+
+use crate::{
+  fun::{Book, Name, Num, Term},
+  maybe_grow, ENTRY_POINT, HVM1_ENTRY_POINT,
+};
+
+/// Reduces a book for as long as it keeps failing.
+///
+/// `fails` is called with each reduced candidate and returns whether the failure still happens.
+/// It should check for the specific failure being debugged, not for any error,
+/// since most reductions leave the book with unbound variables or undefined references.
+///
+/// The reductions are tried one at a time, keeping each one that preserves the failure,
+/// until none of them applies:
+/// * Removing a definition that isn't an entrypoint.
+/// * Removing a rule of a definition with more than one rule.
+/// * Replacing a term of a rule by `*`, by `0` or by one of its children.
+///
+/// Builtin definitions are never changed, since they're not part of the program being debugged.
+/// If the book doesn't fail to begin with, it's returned unchanged.
+pub fn minimize_failure(book: &Book, mut fails: impl FnMut(&Book) -> bool) -> Book {
+  let mut book = book.clone();
+  if !fails(&book) {
+    return book;
+  }
+  loop {
+    let removed_defs = remove_defs(&mut book, &mut fails);
+    let removed_rules = remove_rules(&mut book, &mut fails);
+    let simplified_terms = simplify_terms(&mut book, &mut fails);
+    if !(removed_defs || removed_rules || simplified_terms) {
+      return book;
+    }
+  }
+}
+
+fn remove_defs(book: &mut Book, fails: &mut impl FnMut(&Book) -> bool) -> bool {
+  let entrypoints =
+    [book.entrypoint.clone(), Some(Name::new(ENTRY_POINT)), Some(Name::new(HVM1_ENTRY_POINT))];
+  let removable = book
+    .defs
+    .values()
+    .filter(|def| !def.builtin && !entrypoints.contains(&Some(def.name.clone())))
+    .map(|def| def.name.clone())
+    .collect::<Vec<_>>();
+
+  let mut changed = false;
+  for def_name in removable {
+    let mut candidate = book.clone();
+    candidate.defs.shift_remove(&def_name);
+    if fails(&candidate) {
+      *book = candidate;
+      changed = true;
+    }
+  }
+  changed
+}
+
+fn remove_rules(book: &mut Book, fails: &mut impl FnMut(&Book) -> bool) -> bool {
+  let def_names =
+    book.defs.values().filter(|def| !def.builtin).map(|def| def.name.clone()).collect::<Vec<_>>();
+
+  let mut changed = false;
+  for def_name in def_names {
+    // From the last rule, so that removing one doesn't shift the ones still to be tried.
+    for rule_idx in (0 .. book.defs[&def_name].rules.len()).rev() {
+      if book.defs[&def_name].rules.len() == 1 {
+        break;
+      }
+      let mut candidate = book.clone();
+      candidate.defs[&def_name].rules.remove(rule_idx);
+      if fails(&candidate) {
+        *book = candidate;
+        changed = true;
+      }
+    }
+  }
+  changed
+}
+
+fn simplify_terms(book: &mut Book, fails: &mut impl FnMut(&Book) -> bool) -> bool {
+  let def_names =
+    book.defs.values().filter(|def| !def.builtin).map(|def| def.name.clone()).collect::<Vec<_>>();
+
+  let mut changed = false;
+  for def_name in def_names {
+    for rule_idx in 0 .. book.defs[&def_name].rules.len() {
+      // The terms are visited in preorder, and a replaced term is tried again in case it can be reduced further.
+      let mut term_idx = 0;
+      while let Some(term) = nth_subterm(&book.defs[&def_name].rules[rule_idx].body, term_idx) {
+        let replaced = term.smaller_terms().into_iter().find_map(|smaller| {
+          let mut candidate = book.clone();
+          let body = &mut candidate.defs[&def_name].rules[rule_idx].body;
+          *nth_subterm_mut(body, &mut term_idx.clone()).unwrap() = smaller;
+          fails(&candidate).then_some(candidate)
+        });
+        if let Some(candidate) = replaced {
+          *book = candidate;
+          changed = true;
+        } else {
+          term_idx += 1;
+        }
+      }
+    }
+  }
+  changed
+}
+
+/// The `n`th term inside `term` in preorder, where `term` itself is the first.
+fn nth_subterm(term: &Term, n: usize) -> Option<&Term> {
+  let mut stack = vec![term];
+  let mut idx = 0;
+  while let Some(term) = stack.pop() {
+    if idx == n {
+      return Some(term);
+    }
+    idx += 1;
+    stack.extend(term.children().rev());
+  }
+  None
+}
+
+fn nth_subterm_mut<'a>(term: &'a mut Term, n: &mut usize) -> Option<&'a mut Term> {
+  maybe_grow(|| {
+    if *n == 0 {
+      return Some(term);
+    }
+    *n -= 1;
+    term.children_mut().find_map(|child| nth_subterm_mut(child, n))
+  })
+}
+
+impl Term {
+  /// The terms that can replace this one while minimizing a book, from the simplest.
+  ///
+  /// They're all smaller than this term, so that the minimization always ends.
+  fn smaller_terms(&self) -> Vec<Term> {
+    match self {
+      Term::Era => vec![],
+      Term::Num { .. } => vec![Term::Era],
+      _ => {
+        let mut terms = vec![Term::Era, Term::Num { val: Num::U24(0) }];
+        terms.extend(self.children().cloned());
+        terms
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    desugar_book,
+    diagnostics::{DiagnosticsConfig, Severity},
+    fun::parser::TermParser,
+    CompileOpts,
+  };
+
+  #[test]
+  fn minimize_unbound_var() {
+    let code = "
+      Foo = λx (+ x 1)
+      Bar = λa λb (Foo (* a y))
+      Baz 0 = 1
+      Baz n = (Bar n (Foo n))
+      main = (Baz (Foo 3))
+    ";
+    let book = TermParser::new(code).parse_book(Book::builtins(), false).unwrap();
+
+    let minimized = minimize_failure(&book, |book| {
+      let mut book = book.clone();
+      let diagnostics_cfg = DiagnosticsConfig::new(Severity::Allow, false);
+      match desugar_book(&mut book, CompileOpts::default(), diagnostics_cfg, None) {
+        Ok(_) => false,
+        Err(e) => e.to_string().contains("Unbound variable 'y'"),
+      }
+    });
+
+    let user_defs =
+      minimized.defs.values().filter(|def| !def.builtin).map(|def| def.to_string()).collect::<Vec<_>>();
+    assert_eq!(user_defs, ["(Bar) = y", "(main) = *"]);
+  }
+}
@@ -8,19 +8,30 @@ use interner::global::{GlobalPool, GlobalString};
 use itertools::Itertools;
 use std::{borrow::Cow, collections::HashMap, hash::Hash, ops::Deref};
 
+pub mod alpha_eq;
 pub mod builtins;
 pub mod check;
+pub mod content_hash;
 pub mod display;
+pub mod eval;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod load_book;
+pub mod minimize;
 pub mod net_to_term;
+pub mod node_histogram;
 pub mod parser;
 pub mod term_to_net;
 pub mod transform;
 
 pub use net_to_term::{net_to_term, ReadbackError};
-pub use term_to_net::{book_to_nets, term_to_net};
+pub use term_to_net::{book_to_nets, book_to_nets_with_progress, term_to_net};
 
 pub static STRINGS: GlobalPool<String> = GlobalPool::new();
+
+/// The name bound by the switch that holds a match arm guard, see [`Term::guarded`].
+const GUARD_BND: &str = "%guard";
+
 #[derive(Debug)]
 pub struct Ctx<'book> {
   pub book: &'book mut Book,
@@ -65,6 +76,17 @@ pub struct Definition {
 pub struct Rule {
   pub pats: Vec<Pattern>,
   pub body: Term,
+  /// Where the rule was written in the source code, if it wasn't generated by the compiler.
+  pub span: Option<Span>,
+}
+
+/// A range of the source code, along with the line and column where it starts, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub line: usize,
+  pub column: usize,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Hash)]
@@ -291,7 +313,7 @@ impl PartialEq<str> for Name {
 
 impl PartialEq<&str> for Name {
   fn eq(&self, other: &&str) -> bool {
-    self == other
+    &**self == *other
   }
 }
 
@@ -463,6 +485,39 @@ impl Term {
     Term::app(fun, Term::Var { nam: arg })
   }
 
+  /// The body of a match arm with a guard, `Ctr if cond: body`.
+  ///
+  /// It's a switch on the condition whose `0` case is filled in by
+  /// [`Ctx::fix_match_terms`] with the arm to fall back to.
+  pub fn guarded(cond: Term, body: Term) -> Self {
+    Term::Swt {
+      arg: Box::new(cond),
+      bnd: Some(Name::new(GUARD_BND)),
+      with: vec![],
+      pred: Some(Name::new(format!("{GUARD_BND}-1"))),
+      arms: vec![Term::Err, body],
+    }
+  }
+
+  /// If this is the body of a guarded match arm, returns the guard and the body.
+  pub fn as_guarded(&self) -> Option<(&Term, &Term)> {
+    match self {
+      Term::Swt { arg, bnd: Some(bnd), arms, .. } if bnd == GUARD_BND && matches!(arms[0], Term::Err) => {
+        Some((arg, &arms[1]))
+      }
+      _ => None,
+    }
+  }
+
+  /// If this is the body of a guarded match arm, replaces it by the body and returns the guard.
+  pub fn take_guard(&mut self) -> Option<Term> {
+    self.as_guarded()?;
+    let Term::Swt { arg, arms, .. } = self else { unreachable!() };
+    let guard = std::mem::take(arg.as_mut());
+    *self = arms.pop().unwrap();
+    Some(guard)
+  }
+
   pub fn r#ref(name: &str) -> Self {
     Term::Ref { nam: Name::new(name) }
   }
@@ -999,6 +1054,31 @@ impl Pattern {
     matches!(self, Pattern::Var(_) | Pattern::Chn(_))
   }
 
+  /// Whether this pattern is a variable or an eraser.
+  pub fn is_var(&self) -> bool {
+    matches!(self, Pattern::Var(_))
+  }
+
+  /// Whether this pattern only has variables, erasers, channels, tuples and superpositions,
+  /// so that it can be bound by a lambda without matching.
+  pub fn is_simple(&self) -> bool {
+    self.iter().all(|pat| matches!(pat, Pattern::Var(_) | Pattern::Chn(_) | Pattern::Fan(..)))
+  }
+
+  /// If this is a constructor pattern, returns the constructor and its field patterns.
+  pub fn as_ctr(&self) -> Option<(&Name, &[Pattern])> {
+    match self {
+      Pattern::Ctr(nam, args) => Some((nam, args)),
+      _ => None,
+    }
+  }
+
+  /// The names of all the variables bound by this pattern and its subpatterns, from left to right.
+  /// Unlike [`Pattern::binds`], erasers are skipped.
+  pub fn binders(&self) -> Vec<&Name> {
+    self.binds().flatten().collect()
+  }
+
   pub fn to_term(&self) -> Term {
     match self {
       Pattern::Var(nam) => Term::var_or_era(nam.clone()),
@@ -1107,6 +1187,13 @@ impl Book {
       Some(nam) => nam,
     }
   }
+
+  /// The constructors of the given ADT with their arities, in declaration order,
+  /// or `None` if there's no ADT with that name.
+  pub fn adt_constructors(&self, adt: &Name) -> Option<Vec<(Name, usize)>> {
+    let adt = self.adts.get(adt)?;
+    Some(adt.ctrs.iter().map(|(ctr, fields)| (ctr.clone(), fields.len())).collect())
+  }
 }
 
 #[test]
@@ -1140,3 +1227,27 @@ fn num_to_from_bits() {
     assert_eq!(b, Num::from_bits(Num::to_bits(&b)));
   }
 }
+
+#[test]
+fn adt_constructors() {
+  let book = parser::TermParser::new("data Tree = (Node left val right) | Leaf")
+    .parse_book(Book::default(), false)
+    .unwrap();
+  assert_eq!(
+    book.adt_constructors(&Name::new("Tree")),
+    Some(vec![(Name::new("Tree/Node"), 3), (Name::new("Tree/Leaf"), 0)])
+  );
+  assert_eq!(book.adt_constructors(&Name::new("Tree/Node")), None);
+}
+
+#[test]
+fn pattern_accessors() {
+  let book = parser::TermParser::new("f (Foo a * (Bar b c)) = *").parse_book(Book::default(), false).unwrap();
+  let pat = &book.defs[&Name::new("f")].rules[0].pats[0];
+  let (ctr, args) = pat.as_ctr().unwrap();
+  assert_eq!(ctr, &Name::new("Foo"));
+  assert_eq!(args.len(), 3);
+  assert!(args[0].is_var() && args[1].is_var() && !args[2].is_var());
+  assert_eq!(args[0].as_ctr(), None);
+  assert_eq!(pat.binders(), [&Name::new("a"), &Name::new("b"), &Name::new("c")]);
+}
@@ -9,11 +9,15 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use super::Num;
 
 /// Converts an Interaction-INet to a Lambda Calculus term
+///
+/// Readback stops descending after `max_depth` nested nodes,
+/// reporting a [`ReadbackError::DepthExceeded`] instead of continuing.
 pub fn net_to_term(
   net: &INet,
   book: &Book,
   labels: &Labels,
   linear: bool,
+  max_depth: usize,
   diagnostics: &mut Diagnostics,
 ) -> Term {
   let mut reader = Reader {
@@ -26,6 +30,8 @@ pub fn net_to_term(
     namegen: Default::default(),
     seen: Default::default(),
     errors: Default::default(),
+    depth: 0,
+    max_depth,
   };
 
   let mut term = reader.read_term(net.enter_port(ROOT));
@@ -73,10 +79,25 @@ pub struct Reader<'a> {
   seen_fans: Scope,
   seen: HashSet<Port>,
   errors: Vec<ReadbackError>,
+  /// How many nodes deep the readback currently is.
+  depth: usize,
+  max_depth: usize,
 }
 
 impl Reader<'_> {
   fn read_term(&mut self, next: Port) -> Term {
+    if self.depth >= self.max_depth {
+      self.error(ReadbackError::DepthExceeded);
+      return Term::Err;
+    }
+
+    self.depth += 1;
+    let term = self.read_node(next);
+    self.depth -= 1;
+    term
+  }
+
+  fn read_node(&mut self, next: Port) -> Term {
     use CtrKind::*;
 
     maybe_grow(|| {
@@ -402,16 +423,7 @@ impl Reader<'_> {
   }
 
   pub fn report_errors(&mut self, diagnostics: &mut Diagnostics) {
-    let mut err_counts = std::collections::HashMap::new();
-    for err in &self.errors {
-      *err_counts.entry(*err).or_insert(0) += 1;
-    }
-
-    for (err, count) in err_counts {
-      let count_msg = if count > 1 { format!(" ({count} occurrences)") } else { "".to_string() };
-      let msg = format!("{}{}", err, count_msg);
-      diagnostics.add_diagnostic(msg.as_str(), Severity::Warning, DiagnosticOrigin::Readback);
-    }
+    report_errors(&self.errors, diagnostics);
   }
 
   /// Returns whether the given port represents a tuple or some other
@@ -616,12 +628,29 @@ impl NameGen {
 
 /* Readback errors */
 
+/// Reports each kind of readback error once, as a warning with the number of occurrences.
+pub fn report_errors(errors: &[ReadbackError], diagnostics: &mut Diagnostics) {
+  let mut err_counts = std::collections::HashMap::new();
+  for err in errors {
+    *err_counts.entry(*err).or_insert(0) += 1;
+  }
+
+  for (err, count) in err_counts {
+    let count_msg = if count > 1 { format!(" ({count} occurrences)") } else { "".to_string() };
+    let msg = format!("{}{}", err, count_msg);
+    diagnostics.add_diagnostic(msg.as_str(), Severity::Warning, DiagnosticOrigin::Readback);
+  }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ReadbackError {
   InvalidNumericMatch,
   InvalidNumericOp,
   ReachedRoot,
   Cyclic,
+  DepthExceeded,
+  /// A variable of the net that isn't used exactly twice.
+  UnpairedVar,
 }
 
 impl PartialEq for ReadbackError {
@@ -649,6 +678,15 @@ impl std::fmt::Display for ReadbackError {
       ReadbackError::Cyclic => {
         write!(f, "Unable to interpret the HVM result as a valid Bend term. (Cyclic Term)")
       }
+      ReadbackError::DepthExceeded => {
+        write!(
+          f,
+          "Unable to interpret the HVM result as a valid Bend term. (Maximum readback depth exceeded)"
+        )
+      }
+      ReadbackError::UnpairedVar => {
+        write!(f, "Unable to interpret the HVM result as a valid Bend term. (Unpaired variable)")
+      }
     }
   }
 }
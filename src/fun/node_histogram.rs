@@ -0,0 +1,84 @@
+use crate::{
+  fun::{display::DisplayFn, Book, FanKind, Pattern, Term},
+  maybe_grow,
+};
+use itertools::Itertools;
+use std::{collections::HashMap, fmt};
+
+/// How many times each kind of term node appears, by variant name.
+pub type NodeHistogram = HashMap<&'static str, usize>;
+
+impl Book {
+  /// Counts the term nodes of every rule of every definition in the book.
+  pub fn node_histogram(&self) -> NodeHistogram {
+    let mut histogram = NodeHistogram::new();
+    for def in self.defs.values() {
+      for rule in &def.rules {
+        rule.body.count_nodes(&mut histogram);
+      }
+    }
+    histogram
+  }
+}
+
+impl Term {
+  /// Counts how many times each kind of node appears in this term.
+  ///
+  /// Duplications (`let {a b} = x`) and superpositions (`{a b}`) are counted
+  /// separately from regular lets and tuples.
+  pub fn node_histogram(&self) -> NodeHistogram {
+    let mut histogram = NodeHistogram::new();
+    self.count_nodes(&mut histogram);
+    histogram
+  }
+
+  fn count_nodes(&self, histogram: &mut NodeHistogram) {
+    maybe_grow(|| {
+      *histogram.entry(self.node_name()).or_default() += 1;
+      for child in self.children() {
+        child.count_nodes(histogram);
+      }
+    })
+  }
+
+  fn node_name(&self) -> &'static str {
+    match self {
+      Term::Lam { .. } => "Lam",
+      Term::Var { .. } => "Var",
+      Term::Link { .. } => "Link",
+      Term::Let { pat: box Pattern::Fan(FanKind::Dup, ..), .. } => "Dup",
+      Term::Let { .. } => "Let",
+      Term::Do { .. } => "Do",
+      Term::Ask { .. } => "Ask",
+      Term::Use { .. } => "Use",
+      Term::App { .. } => "App",
+      Term::Fan { fan: FanKind::Dup, .. } => "Sup",
+      Term::Fan { fan: FanKind::Tup, .. } => "Tup",
+      Term::Num { .. } => "Num",
+      Term::Nat { .. } => "Nat",
+      Term::Str { .. } => "Str",
+      Term::List { .. } => "List",
+      Term::Oper { .. } => "Oper",
+      Term::Mat { .. } => "Mat",
+      Term::Swt { .. } => "Swt",
+      Term::Fold { .. } => "Fold",
+      Term::Bend { .. } => "Bend",
+      Term::Open { .. } => "Open",
+      Term::Ref { .. } => "Ref",
+      Term::Era => "Era",
+      Term::Err => "Err",
+    }
+  }
+}
+
+/// Displays a node histogram as a table, with the most common nodes first.
+pub fn display_histogram(histogram: &NodeHistogram) -> impl fmt::Display + '_ {
+  DisplayFn(move |f| {
+    let total = histogram.values().sum::<usize>();
+    let width = total.to_string().len();
+    for (name, count) in histogram.iter().sorted_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b))) {
+      writeln!(f, "{name:<5} {count:>width$}")?;
+    }
+    writeln!(f, "{:<5} {total:>width$}", "Total")
+  })
+}
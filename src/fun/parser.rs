@@ -1,18 +1,20 @@
 use crate::{
   fun::{
     display::DisplayFn, Adt, Book, CtrField, Definition, FanKind, MatchRule, Name, Num, Op, Pattern, Rule,
-    Tag, Term, STRINGS,
+    Span, Tag, Term, STRINGS,
   },
   imp::parser::PyParser,
-  maybe_grow,
+  maybe_grow, ENTRY_POINT,
 };
 use highlight_error::highlight_error;
+use std::ops::Range;
 use TSPL::Parser;
 
 // Bend grammar description:
 // <Book>       ::= (<Data> | <Rule>)*
 // <Data>       ::= "data" <Name> "=" ( <Name> | "(" <Name> (<Name>)* ")" )+
-// <Rule>       ::= ("(" <Name> <Pattern>* ")" | <Name> <Pattern>*) "=" <Term>
+// <Rule>       ::= ("(" <Name> <Pattern>* ")" | <Name> <Pattern>*) "=" <Term> <Where>?
+// <Where>      ::= "where" "{" (<Name> <Pattern>* "=" <Term> ";"?)* "}"
 // <Pattern>    ::= "(" <Name> <Pattern>* ")" | <NameEra> | <Number> | "(" <Pattern> ("," <Pattern>)+ ")"
 // <Term>       ::=
 //   <Number> | <NumOp> | <Tup> | <App> | <Group> | <Nat> | <Lam> | <UnscopedLam> | <Bend> | <Fold> |
@@ -31,9 +33,10 @@ use TSPL::Parser;
 // <Dup>        ::= "let" <Tag>? "{" <NameEra> (","? <NameEra>)+ "}" "=" <Term> ";"? <Term>
 // <List>       ::= "[" (<Term> ","?)* "]"
 // <String>     ::= "\"" (escape sequence | [^"])* "\""
+// <RawString>  ::= "\"\"\"" (any text without "\"\"\"") "\"\"\""
 // <Char>       ::= "'" (escape sequence | [^']) "'"
 // <Match>      ::= "match" <Name> ("=" <Term>)? ("with" <Var> (","? <Var>)*)? "{" <MatchArm>+ "}"
-// <MatchArm>   ::= "|"? <Pattern> ":" <Term> ";"?
+// <MatchArm>   ::= "|"? <Pattern> ("if" <Term>)? ":" <Term> ";"?
 // <Switch>     ::= "switch" <Name> ("=" <Term>)? ("with" <Var> (","? <Var>)*)? "{" <SwitchArm>+ "}"
 // <SwitchArm>  ::= "|"? (<Num>|"_") ":" <Term> ";"?
 // <Var>        ::= <Name>
@@ -45,16 +48,66 @@ use TSPL::Parser;
 // <Number>     ::= ([0-9]+ | "0x"[0-9a-fA-F]+ | "0b"[01]+)
 // <Operator>   ::= ( "+" | "-" | "*" | "/" | "%" | "==" | "!=" | "<<" | ">>" | "<=" | ">=" | "<" | ">" | "^" )
 
+/// The keywords that start a term, which can't start a definition.
+const TERM_KEYWORDS: &[&str] = &["use", "let", "ask", "if", "match", "switch", "do", "fold", "bend", "open"];
+
 pub type ParseResult<T> = std::result::Result<T, String>;
 
 pub struct TermParser<'i> {
   input: &'i str,
   index: usize,
+  error_ctx: Option<(Range<usize>, ParseErrorKind)>,
+  /// Whether a top-level expression was already parsed as the body of `main`.
+  top_level_expr: bool,
+}
+
+/// A syntax error, along with where in the input it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+  pub kind: ParseErrorKind,
+  /// The error message, including the highlighted input.
+  pub msg: String,
+  /// The byte range of the input the error points to.
+  pub span: Range<usize>,
+  /// The line where the span starts, counting from 1.
+  pub line: usize,
+  /// The column where the span starts, in chars and counting from 1.
+  pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParseErrorKind {
+  /// Found something other than what was expected.
+  UnexpectedToken,
+  /// The input ended while something was still expected.
+  UnexpectedEof,
+  /// A string literal without its closing quotes.
+  UnterminatedString,
+  /// A line indented differently than its block.
+  Indentation,
+  /// Well-formed input that isn't allowed, like a redefinition or a reserved name.
+  Invalid,
 }
 
 impl<'a> TermParser<'a> {
   pub fn new(input: &'a str) -> Self {
-    Self { input, index: 0 }
+    Self { input, index: 0, error_ctx: None, top_level_expr: false }
+  }
+
+  /// Like [Self::parse_book], but the error also says where in the input it was found.
+  pub fn parse_book_located(&mut self, default_book: Book, builtin: bool) -> Result<Book, ParseError> {
+    self.error_ctx = None;
+    self.parse_book(default_book, builtin).map_err(|msg| self.locate_error(msg))
+  }
+
+  /// Attaches to an error message the span and kind of the last error found.
+  ///
+  /// Errors that don't point to a specific part of the input are located at the current position.
+  fn locate_error(&mut self, msg: String) -> ParseError {
+    let index = self.index.min(self.input.len());
+    let (span, kind) = self.error_ctx.take().unwrap_or((index .. index + 1, ParseErrorKind::Invalid));
+    let Span { line, column, .. } = self.span(span.start, span.end);
+    ParseError { kind, msg, span, line, column }
   }
 
   /* AST parsing functions */
@@ -63,52 +116,105 @@ impl<'a> TermParser<'a> {
     let mut book = default_book;
     let mut indent = self.advance_newlines();
     while !self.is_eof() {
-      let ini_idx = *self.index();
-      // Imp type definition
-      if self.try_parse_keyword("type") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
+      indent = self.parse_top_level(&mut book, indent, builtin)?;
+    }
+
+    Ok(book)
+  }
+
+  /// Parses a single top-level item into the book, returning the indentation of the next one.
+  fn parse_top_level(&mut self, book: &mut Book, indent: Indent, builtin: bool) -> ParseResult<Indent> {
+    let ini_idx = *self.index();
+    // Imp type definition
+    if self.try_parse_keyword("type") {
+      return self.parse_imp(|prs| {
         let (enum_, nxt_indent) = prs.parse_type(indent)?;
-        self.index = prs.index;
-        let end_idx = *self.index();
-        prs.add_type(enum_, &mut book, ini_idx, end_idx, builtin)?;
-        indent = nxt_indent;
-        continue;
-      }
-      // Imp record type definition
-      if self.try_parse_keyword("object") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
+        let end_idx = prs.index;
+        prs.add_type(enum_, book, ini_idx, end_idx, builtin)?;
+        Ok(nxt_indent)
+      });
+    }
+    // Imp record type definition
+    if self.try_parse_keyword("object") {
+      return self.parse_imp(|prs| {
         let (obj, nxt_indent) = prs.parse_object(indent)?;
-        self.index = prs.index;
-        let end_idx = *self.index();
-        prs.add_object(obj, &mut book, ini_idx, end_idx, builtin)?;
-        indent = nxt_indent;
-        continue;
-      }
-      // Imp function definition
-      if self.try_parse_keyword("def") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
+        let end_idx = prs.index;
+        prs.add_object(obj, book, ini_idx, end_idx, builtin)?;
+        Ok(nxt_indent)
+      });
+    }
+    // Imp function definition
+    if self.try_parse_keyword("def") {
+      return self.parse_imp(|prs| {
         let (def, nxt_indent) = prs.parse_def(indent)?;
-        self.index = prs.index;
-        let end_idx = *self.index();
-        prs.add_def(def, &mut book, ini_idx, end_idx)?;
-        indent = nxt_indent;
-        continue;
-      }
-      // Fun type definition
-      if self.try_parse_keyword("data") {
-        let (nam, adt) = self.parse_datatype(builtin)?;
-        let end_idx = *self.index();
-        self.with_ctx(book.add_adt(nam, adt), ini_idx, end_idx)?;
-        indent = self.advance_newlines();
-        continue;
+        let end_idx = prs.index;
+        prs.add_def(def, book, ini_idx, end_idx)?;
+        Ok(nxt_indent)
+      });
+    }
+    // Fun type definition
+    if self.try_parse_keyword("data") {
+      let (nam, adt) = self.parse_datatype(builtin)?;
+      let end_idx = *self.index();
+      self.with_ctx(book.add_adt(nam, adt), ini_idx, end_idx)?;
+      return Ok(self.advance_newlines());
+    }
+    // Fun function definition, or an expression that becomes the body of `main`.
+    // Items that start with a term keyword are tried as an expression first,
+    // since they could still be a definition with that name.
+    let expr_first = !builtin && self.starts_with_term_keyword();
+    let expr = if expr_first { self.try_parse_top_level_expr(ini_idx) } else { None };
+    let (name, rule, is_expr) = match expr {
+      Some(rule) => (Name::new(ENTRY_POINT), rule, true),
+      None => match self.parse_rule() {
+        Ok((name, rule)) => (name, rule, false),
+        Err(err) if !builtin && !expr_first => {
+          let (rule_idx, rule_ctx) = (self.index, self.error_ctx.take());
+          let Some(rule) = self.try_parse_top_level_expr(ini_idx) else {
+            // Not an expression either, so report the error of the definition it most likely was meant to be.
+            self.index = rule_idx;
+            self.error_ctx = rule_ctx;
+            return Err(err);
+          };
+          (Name::new(ENTRY_POINT), rule, true)
+        }
+        Err(err) => return Err(err),
+      },
+    };
+    if name == ENTRY_POINT && (self.top_level_expr || (is_expr && book.defs.contains_key(&name))) {
+      let end_idx = rule.span.map_or(*self.index(), |span| span.end);
+      let msg = format!("The top-level expression conflicts with the definition of '{ENTRY_POINT}'.");
+      return self.with_ctx(Err(msg), ini_idx, end_idx);
+    }
+    self.top_level_expr |= is_expr;
+    book.add_rule(name, rule, builtin);
+    Ok(self.advance_newlines())
+  }
+
+  /// Parses a top-level item as an expression, to use as the body of `main`.
+  ///
+  /// The expression must end its line. If it doesn't, nothing is consumed.
+  fn try_parse_top_level_expr(&mut self, ini_idx: usize) -> Option<Rule> {
+    self.index = ini_idx;
+    if let Ok(body) = self.parse_term() {
+      let end_idx = *self.index();
+      self.skip_trivia_inline();
+      if self.is_eof() || self.starts_with("\n") || self.starts_with("\r") {
+        self.error_ctx = None;
+        return Some(Rule { pats: vec![], body, span: Some(self.span(ini_idx, end_idx)) });
       }
-      // Fun function definition
-      let (name, rule) = self.parse_rule()?;
-      book.add_rule(name, rule, builtin);
-      indent = self.advance_newlines();
     }
+    self.index = ini_idx;
+    self.error_ctx = None;
+    None
+  }
 
-    Ok(book)
+  /// Whether the input continues with a keyword that starts a term, and not a definition.
+  fn starts_with_term_keyword(&mut self) -> bool {
+    let ini_idx = *self.index();
+    let is_keyword = TERM_KEYWORDS.iter().any(|keyword| self.try_parse_keyword(keyword));
+    self.index = ini_idx;
+    is_keyword
   }
 
   fn parse_datatype(&mut self, builtin: bool) -> ParseResult<(Name, Adt)> {
@@ -153,6 +259,7 @@ impl<'a> TermParser<'a> {
   fn parse_rule(&mut self) -> ParseResult<(Name, Rule)> {
     // (name pat*) = term
     // name pat* = term
+    let ini_idx = *self.index();
     let (name, pats) = if self.try_consume_exactly("(") {
       self.skip_trivia();
       let name = self.labelled(|p| p.parse_top_level_name(), "function name")?;
@@ -169,12 +276,79 @@ impl<'a> TermParser<'a> {
       (name, pats)
     };
 
-    let body = self.parse_term()?;
+    let mut body = self.parse_term()?;
+    let mut end_idx = *self.index();
 
-    let rule = Rule { pats, body };
+    self.skip_trivia();
+    if self.try_parse_keyword("where") {
+      body = self.parse_where(body)?;
+      end_idx = *self.index();
+    }
+
+    let rule = Rule { pats, body, span: Some(self.span(ini_idx, end_idx)) };
     Ok((name, rule))
   }
 
+  /// Parses the local definitions of a `where` clause, desugaring them into lets around the rule body.
+  ///
+  /// Each definition can see the rule variables and the definitions before it,
+  /// and the arguments of a definition become lambdas.
+  ///
+  /// Example:
+  /// ```bend
+  /// f x = (g (h x)) where { g y = (+ y 1); h = λz (* z 2) }
+  ///
+  /// // Desugars to:
+  /// f x = let g = λy (+ y 1); let h = λz (* z 2); (g (h x))
+  /// ```
+  ///
+  /// Since they're only lets, local definitions can't be recursive, can't match on
+  /// constructors in their arguments and can't have more than one rule.
+  fn parse_where(&mut self, body: Term) -> ParseResult<Term> {
+    self.consume("{")?;
+    let mut defs: Vec<(Name, Term)> = vec![];
+    while !self.try_consume("}") {
+      let ini_idx = *self.index();
+      let name = self.labelled(|p| p.parse_bend_name(), "local definition name")?;
+      let end_idx = *self.index();
+      if defs.iter().any(|(nam, _)| *nam == name) {
+        let msg =
+          format!("Local definition '{name}' has more than one rule, which 'where' clauses don't support.");
+        return self.with_ctx(Err(msg), ini_idx, end_idx);
+      }
+      let mut pats = vec![];
+      self.skip_trivia();
+      while !self.try_consume("=") {
+        let pat_ini_idx = *self.index();
+        let pat = self.parse_pattern(false)?;
+        let pat_end_idx = *self.index();
+        if !pat.is_simple() {
+          let msg = format!(
+            "Local definition '{name}' matches on a constructor or number, which 'where' clauses don't support."
+          );
+          return self.with_ctx(Err(msg), pat_ini_idx, pat_end_idx);
+        }
+        pats.push(pat);
+        self.skip_trivia();
+      }
+      let val = pats.into_iter().rfold(self.parse_term()?, |bod, pat| Term::lam(pat, bod));
+      if val.free_vars().contains_key(&name) {
+        let end_idx = *self.index();
+        let msg = format!("Local definition '{name}' is recursive, which 'where' clauses don't support.");
+        return self.with_ctx(Err(msg), ini_idx, end_idx);
+      }
+      self.try_consume(";");
+      defs.push((name, val));
+    }
+
+    let body = defs.into_iter().rfold(body, |nxt, (nam, val)| Term::Let {
+      pat: Box::new(Pattern::Var(Some(nam))),
+      val: Box::new(val),
+      nxt: Box::new(nxt),
+    });
+    Ok(body)
+  }
+
   fn parse_pattern(&mut self, simple: bool) -> ParseResult<Pattern> {
     maybe_grow(|| {
       let (tag, unexpected_tag) = self.parse_tag()?;
@@ -221,7 +395,7 @@ impl<'a> TermParser<'a> {
       // String
       if self.starts_with("\"") && !simple {
         unexpected_tag(self)?;
-        let str = self.parse_quoted_string()?;
+        let str = self.parse_string_lit()?;
         return Ok(Pattern::Str(STRINGS.get(str)));
       }
 
@@ -239,6 +413,12 @@ impl<'a> TermParser<'a> {
         return Ok(Pattern::Num(num));
       }
 
+      // Signed number
+      if self.starts_with_signed_number() {
+        unexpected_tag(self)?;
+        return self.signed_number_pattern_err();
+      }
+
       // Channel
       if self.starts_with("$") {
         unexpected_tag(self)?;
@@ -276,7 +456,7 @@ impl<'a> TermParser<'a> {
         // Opr but maybe a tup
         self.skip_trivia();
         let starts_with_oper = self.peek_one().map_or(false, |c| "+-*/%&|<>^=!".contains(c));
-        if starts_with_oper {
+        if starts_with_oper && !self.is_signed_number_element() {
           let opr = self.parse_oper()?;
 
           // jk, actually a tuple
@@ -362,7 +542,7 @@ impl<'a> TermParser<'a> {
       // String
       if self.starts_with("\"") {
         unexpected_tag(self)?;
-        let str = self.parse_quoted_string()?;
+        let str = self.parse_string_lit()?;
         return Ok(Term::Str { val: STRINGS.get(str) });
       }
 
@@ -639,11 +819,60 @@ impl<'a> TermParser<'a> {
   fn parse_match_arm(&mut self) -> ParseResult<MatchRule> {
     self.try_consume("|");
     self.skip_trivia();
+    if self.starts_with_signed_number() {
+      return self.signed_number_pattern_err();
+    }
     let nam = self.parse_name_or_era()?;
+    self.skip_trivia();
+    let guard = if self.try_parse_keyword("if") { Some(self.parse_term()?) } else { None };
     self.consume(":")?;
     let bod = self.parse_term()?;
+    let bod = if let Some(guard) = guard { Term::guarded(guard, bod) } else { bod };
     Ok((nam, vec![], bod))
   }
+
+  /// Whether the input continues with a signed number literal that is a whole element of a tuple
+  /// or parenthesized term, like in `(-5, x)` or `(+5)`, instead of the operator of a numeric operation.
+  fn is_signed_number_element(&mut self) -> bool {
+    if !self.starts_with_signed_number() {
+      return false;
+    }
+    let ini_idx = *self.index();
+    let is_element = self.parse_number().is_ok() && {
+      self.skip_trivia();
+      self.starts_with(",") || self.starts_with(")")
+    };
+    *self.index() = ini_idx;
+    *self.error_ctx() = None;
+    is_element
+  }
+
+  /// Whether the input continues with a sign followed by a digit.
+  /// Since '-' is also valid in names, this must be checked before parsing a name.
+  fn starts_with_signed_number(&mut self) -> bool {
+    (self.starts_with("-") || self.starts_with("+"))
+      && self.input()[*self.index() + 1 ..].starts_with(|c: char| c.is_ascii_digit())
+  }
+
+  /// Runs an imp parser from the current position, continuing after whatever it parsed.
+  fn parse_imp<T>(&mut self, parse: impl FnOnce(&mut PyParser<'a>) -> ParseResult<T>) -> ParseResult<T> {
+    let mut prs = PyParser { input: self.input, index: self.index, error_ctx: None };
+    let res = parse(&mut prs);
+    self.index = prs.index;
+    self.error_ctx = prs.error_ctx;
+    res
+  }
+
+  fn signed_number_pattern_err<T>(&mut self) -> ParseResult<T> {
+    let ini_idx = *self.index();
+    self.parse_number()?;
+    let end_idx = *self.index();
+    let lit = &self.input()[ini_idx .. end_idx];
+    let msg = format!(
+      "\x1b[1mSigned number '{lit}' used as a pattern.\x1b[0m\nOnly natural numbers can be matched on, use '==' to compare with '{lit}' instead."
+    );
+    self.with_ctx(Err(msg), ini_idx, end_idx)
+  }
 }
 
 impl<'a> Parser<'a> for TermParser<'a> {
@@ -747,7 +976,12 @@ impl Book {
             if self.adts.get(e.get()).is_some_and(|adt| adt.builtin) {
               return Err(format!("{} is a built-in constructor and should not be overridden.", e.key()));
             } else {
-              return Err(format!("Repeated constructor '{}'", e.key()));
+              return Err(format!(
+                "Repeated constructor '{}' in data types '{}' and '{}'",
+                e.key(),
+                e.get(),
+                nam
+              ));
             }
           }
         }
@@ -766,9 +1000,96 @@ impl Book {
   }
 }
 
-impl<'a> ParserCommons<'a> for TermParser<'a> {}
+/// Parses a book one definition at a time, so that the definitions can be
+/// processed without having all of them in memory at once.
+///
+/// Yields each parsed definition, with its rules if they're written one after the other.
+/// Rules of the same definition that are apart from each other are yielded as separate definitions.
+///
+/// The declared types are kept, since parsing the following definitions depends on them,
+/// and can be retrieved with [`DefinitionStream::into_book`] at the end.
+/// Since yielded definitions are not kept, redefinitions of them are not detected.
+///
+/// Stops at the first error, which is yielded after the definitions parsed before it.
+pub struct DefinitionStream<'a> {
+  parser: TermParser<'a>,
+  book: Book,
+  /// The definitions from the default book, which are not yielded.
+  default_defs: usize,
+  indent: Option<Indent>,
+  builtin: bool,
+  /// An error to yield after the definitions parsed before it.
+  error: Option<String>,
+}
+
+impl<'a> DefinitionStream<'a> {
+  pub fn new(input: &'a str, default_book: Book, builtin: bool) -> Self {
+    let mut parser = TermParser::new(input);
+    let indent = Some(parser.advance_newlines());
+    let default_defs = default_book.defs.len();
+    Self { parser, book: default_book, default_defs, indent, builtin, error: None }
+  }
+
+  /// Returns the default book with the types declared in the parsed source.
+  pub fn into_book(self) -> Book {
+    self.book
+  }
+
+  fn pop_parsed(&mut self) -> Option<Definition> {
+    self.book.defs.shift_remove_index(self.default_defs).map(|(_, def)| def)
+  }
+}
+
+impl Iterator for DefinitionStream<'_> {
+  type Item = ParseResult<Definition>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      // The last parsed definition may still get more rules, so it's only yielded once something else follows it.
+      if self.book.defs.len() > self.default_defs + 1 {
+        return self.pop_parsed().map(Ok);
+      }
+
+      let Some(indent) = self.indent else {
+        return self.pop_parsed().map(Ok).or_else(|| self.error.take().map(Err));
+      };
+      if self.parser.is_eof() {
+        self.indent = None;
+        continue;
+      }
+
+      match self.parser.parse_top_level(&mut self.book, indent, self.builtin) {
+        Ok(indent) => self.indent = Some(indent),
+        Err(err) => {
+          self.indent = None;
+          self.error = Some(err);
+        }
+      }
+    }
+  }
+}
+
+impl<'a> ParserCommons<'a> for TermParser<'a> {
+  fn error_ctx(&mut self) -> &mut Option<(Range<usize>, ParseErrorKind)> {
+    &mut self.error_ctx
+  }
+}
 
 pub trait ParserCommons<'a>: Parser<'a> {
+  /// The span of the input highlighted by the last error and its kind, to locate it in [ParseError].
+  fn error_ctx(&mut self) -> &mut Option<(Range<usize>, ParseErrorKind)>;
+
+  /// The span of the input between two indexes, without the whitespace at its end.
+  fn span(&mut self, ini_idx: usize, end_idx: usize) -> Span {
+    let input = self.input();
+    let start = ini_idx.min(input.len());
+    let end = start + input[start .. end_idx.clamp(start, input.len())].trim_end().len();
+    let before = &input[.. start];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    Span { start, end, line, column }
+  }
+
   fn labelled<T>(&mut self, parser: impl Fn(&mut Self) -> ParseResult<T>, label: &str) -> ParseResult<T> {
     match parser(self) {
       Ok(val) => Ok(val),
@@ -874,7 +1195,8 @@ pub trait ParserCommons<'a>: Parser<'a> {
     let is_eof = self.is_eof();
     let detected = DisplayFn(|f| if is_eof { write!(f, " end of input") } else { Ok(()) });
     let msg = format!("\x1b[1m- expected:\x1b[0m {}\n\x1b[1m- detected:\x1b[0m{}", exp, detected);
-    self.with_ctx(Err(msg), ini_idx, end_idx)
+    let kind = if is_eof { ParseErrorKind::UnexpectedEof } else { ParseErrorKind::UnexpectedToken };
+    self.with_ctx_kind(Err(msg), kind, ini_idx, end_idx)
   }
 
   fn with_ctx<T>(
@@ -882,8 +1204,20 @@ pub trait ParserCommons<'a>: Parser<'a> {
     res: Result<T, impl std::fmt::Display>,
     ini_idx: usize,
     end_idx: usize,
+  ) -> ParseResult<T> {
+    self.with_ctx_kind(res, ParseErrorKind::Invalid, ini_idx, end_idx)
+  }
+
+  /// Like [Self::with_ctx], but for errors of a specific [ParseErrorKind].
+  fn with_ctx_kind<T>(
+    &mut self,
+    res: Result<T, impl std::fmt::Display>,
+    kind: ParseErrorKind,
+    ini_idx: usize,
+    end_idx: usize,
   ) -> ParseResult<T> {
     res.map_err(|msg| {
+      *self.error_ctx() = Some((ini_idx .. end_idx, kind));
       let ctx = highlight_error(ini_idx, end_idx, self.input());
       format!("{msg}\n{ctx}")
     })
@@ -1050,6 +1384,17 @@ pub trait ParserCommons<'a>: Parser<'a> {
   }
 
   fn parse_u32(&mut self) -> ParseResult<u32> {
+    let ini_idx = *self.index();
+    let num = self.parse_digits()?;
+    if num > u32::MAX as u64 {
+      return self.num_range_err(ini_idx, "U32", 0, u32::MAX as i64);
+    }
+    Ok(num as u32)
+  }
+
+  /// Parses the digits of a number literal.
+  /// Values that don't fit in a u64 saturate to `u64::MAX`, since they're outside of every range.
+  fn parse_digits(&mut self) -> ParseResult<u64> {
     let radix = match self.peek_many(2) {
       Some("0x") => {
         self.advance_many(2);
@@ -1066,8 +1411,25 @@ pub trait ParserCommons<'a>: Parser<'a> {
     if num_str.is_empty() {
       self.expected("numeric digit")
     } else {
-      u32::from_str_radix(&num_str, radix).map_err(|e| e.to_string())
+      Ok(u64::from_str_radix(&num_str, radix).unwrap_or(u64::MAX))
+    }
+  }
+
+  /// Parses the decimal digits after the point of a float literal.
+  /// Digits past the precision of a float don't change the result, instead of making it overflow.
+  fn parse_fraction(&mut self) -> ParseResult<f32> {
+    let digits = self.take_while(|c| c.is_ascii_digit() || c == '_');
+    let mut digits = digits.chars().filter_map(|c| c.to_digit(10)).peekable();
+    if digits.peek().is_none() {
+      return self.expected("numeric digit");
+    }
+    let mut fra = 0f64;
+    let mut scale = 1f64;
+    for digit in digits {
+      scale /= 10.0;
+      fra += digit as f64 * scale;
     }
+    Ok(fra as f32)
   }
 
   fn parse_number(&mut self) -> ParseResult<Num> {
@@ -1083,18 +1445,13 @@ pub trait ParserCommons<'a>: Parser<'a> {
     };
 
     // Parses main value
-    let num = self.parse_u32()?;
+    let num = self.parse_digits()?;
 
     // Parses frac value (Float type)
-    // TODO: Will lead to some rounding errors
     // TODO: Doesn't cover very large/small numbers
     let fra = if let Some('.') = self.peek_one() {
       self.advance_one();
-      let ini_idx = *self.index();
-      let fra = self.parse_u32()? as f32;
-      let end_idx = *self.index();
-      let fra = fra / 10f32.powi((end_idx - ini_idx) as i32);
-      Some(fra)
+      Some(self.parse_fraction()?)
     } else {
       None
     };
@@ -1107,23 +1464,26 @@ pub trait ParserCommons<'a>: Parser<'a> {
 
     // I24
     if let Some(sgn) = sgn {
-      let num = sgn * num as i32;
+      let num = i64::try_from(num).map_or(i64::MAX, |num| sgn * num);
       if !(-0x00800000 ..= 0x007fffff).contains(&num) {
-        return self.num_range_err(ini_idx, "I24");
+        return self.num_range_err(ini_idx, "I24", -0x00800000, 0x007fffff);
       }
-      return Ok(Num::I24(num));
+      return Ok(Num::I24(num as i32));
     }
 
     // U24
     if num >= 1 << 24 {
-      return self.num_range_err(ini_idx, "U24");
+      return self.num_range_err(ini_idx, "U24", 0, 0x00ffffff);
     }
-    Ok(Num::U24(num))
+    Ok(Num::U24(num as u32))
   }
 
-  fn num_range_err<T>(&mut self, ini_idx: usize, typ: &str) -> ParseResult<T> {
-    let msg = format!("\x1b[1mNumber literal outside of range for {}.\x1b[0m", typ);
+  fn num_range_err<T>(&mut self, ini_idx: usize, typ: &str, min: i64, max: i64) -> ParseResult<T> {
     let end_idx = *self.index();
+    let lit = &self.input()[ini_idx .. end_idx];
+    let msg = format!(
+      "\x1b[1mNumber literal '{lit}' outside of range for {typ}.\x1b[0m\nValues must be between {min} and {max}."
+    );
     self.with_ctx(Err(msg), ini_idx, end_idx)
   }
 
@@ -1153,4 +1513,78 @@ pub trait ParserCommons<'a>: Parser<'a> {
     self.consume_exactly("`")?;
     Ok(result)
   }
+
+  /// Parses a quoted string, or a raw string surrounded by `"""`.
+  ///
+  /// Raw strings can span multiple lines and contain quotes,
+  /// and their contents are kept as they are, without processing escapes.
+  fn parse_string_lit(&mut self) -> ParseResult<String> {
+    self.skip_trivia();
+    if !self.starts_with("\"\"\"") {
+      return self.parse_quoted_string();
+    }
+
+    let ini_idx = *self.index();
+    let contents = &self.input()[ini_idx + 3 ..];
+    let Some(len) = contents.find("\"\"\"") else {
+      let msg = "Unterminated raw string literal, expected a closing '\"\"\"'.";
+      return self.with_ctx_kind(Err(msg), ParseErrorKind::UnterminatedString, ini_idx, ini_idx + 3);
+    };
+    let str = contents[.. len].to_string();
+    *self.index() = ini_idx + 3 + len + 3;
+    Ok(str)
+  }
+}
+
+#[test]
+fn parse_error_location() {
+  let code = "main = 1\n\nfoo = (bar baz\n";
+  let err = TermParser::new(code).parse_book_located(Book::default(), false).unwrap_err();
+  assert_eq!(err.kind, ParseErrorKind::UnexpectedEof);
+  assert_eq!((err.line, err.column), (4, 1));
+  assert_eq!(err.span, code.len() .. code.len() + 1);
+
+  let code = "main = 1\n\ndef foo():\n  return 1 +\n";
+  let err = TermParser::new(code).parse_book_located(Book::default(), false).unwrap_err();
+  assert_eq!(err.line, 4);
+  assert_eq!(&code[err.span.clone()], "\n");
+
+  let code = "main = λx (x ]";
+  let err = TermParser::new(code).parse_book_located(Book::default(), false).unwrap_err();
+  assert_eq!(err.kind, ParseErrorKind::UnexpectedToken);
+  assert_eq!((err.line, err.column), (1, 14));
+  assert_eq!(&code[err.span], "]");
+}
+
+#[test]
+fn parse_error_kind() {
+  let code = "def main():\n  return foo(1 = 2)\n";
+  let err = TermParser::new(code).parse_book_located(Book::default(), false).unwrap_err();
+  assert_eq!(err.kind, ParseErrorKind::UnexpectedToken);
+}
+
+#[test]
+fn unterminated_raw_string() {
+  let code = "main = 1\n\nfoo = (bar \"\"\"baz\n\" \"\"\n";
+  let err = TermParser::new(code).parse_book_located(Book::default(), false).unwrap_err();
+  assert_eq!(err.kind, ParseErrorKind::UnterminatedString);
+  assert!(err.msg.starts_with("Unterminated raw string literal"));
+  assert_eq!((err.line, err.column), (3, 12));
+  assert_eq!(&code[err.span], "\"\"\"");
+}
+
+#[test]
+fn rule_spans() {
+  let code = "Foo 0 = 1\nFoo n = (Foo (- n 1))  # rec\n\ndef bar(x):\n  return x\n\nmain = (Foo 2)\n";
+  let book = TermParser::new(code).parse_book(Book::default(), false).unwrap();
+  let spans =
+    |name: &str| book.defs[&Name::new(name)].rules.iter().map(|r| r.span.unwrap()).collect::<Vec<_>>();
+
+  let rec = spans("Foo");
+  assert_eq!(rec.iter().map(|s| (s.line, s.column)).collect::<Vec<_>>(), [(1, 1), (2, 1)]);
+  assert_eq!(&code[rec[1].start .. rec[1].end], "Foo n = (Foo (- n 1))");
+  let bar = spans("bar");
+  assert_eq!(&code[bar[0].start .. bar[0].end], "def bar(x):\n  return x");
+  let main = spans("main");
+  assert_eq!((main[0].line, &code[main[0].start .. main[0].end]), (7, "main = (Foo 2)"));
 }
@@ -10,6 +10,7 @@ use std::{
 };
 
 use hvmc::ast::{Net, Tree};
+use itertools::Itertools;
 use loaned::LoanedMut;
 
 use super::{num_to_name, FanKind, Op};
@@ -18,6 +19,16 @@ use super::{num_to_name, FanKind, Op};
 pub struct ViciousCycleErr;
 
 pub fn book_to_nets(book: &Book, diags: &mut Diagnostics) -> Result<(hvmc::ast::Book, Labels), Diagnostics> {
+  book_to_nets_with_progress(book, diags, |_, _| ())
+}
+
+/// Like [book_to_nets], but calls `progress` with the index of each definition before converting it
+/// and the total number of definitions.
+pub fn book_to_nets_with_progress(
+  book: &Book,
+  diags: &mut Diagnostics,
+  mut progress: impl FnMut(usize, usize),
+) -> Result<(hvmc::ast::Book, Labels), Diagnostics> {
   diags.start_pass();
 
   let mut hvmc = hvmc::ast::Book::default();
@@ -25,7 +36,8 @@ pub fn book_to_nets(book: &Book, diags: &mut Diagnostics) -> Result<(hvmc::ast::
 
   let main = book.entrypoint.as_ref().unwrap();
 
-  for def in book.defs.values() {
+  for (idx, def) in book.defs.values().enumerate() {
+    progress(idx, book.defs.len());
     for rule in def.rules.iter() {
       let net = term_to_net(&rule.body, &mut labels);
 
@@ -358,6 +370,46 @@ impl IndexMut<FanKind> for Labels {
   }
 }
 
+/// Text representation of the named labels, one `<fan> <label> <name>` per line.
+/// Used to store the labels of a compiled book alongside it.
+impl std::fmt::Display for Labels {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for (kind, generator) in [("con", &self.con), ("dup", &self.dup), ("tup", &self.tup)] {
+      for (label, name) in generator.label_to_name.iter().sorted() {
+        writeln!(f, "{kind} {label} {name}")?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl std::str::FromStr for Labels {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut labels = Labels::default();
+    for line in s.lines().filter(|line| !line.trim().is_empty()) {
+      let mut parts = line.split_whitespace();
+      let (Some(kind), Some(label), Some(name), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+      else {
+        return Err(format!("Invalid label entry '{line}'"));
+      };
+      let generator = match kind {
+        "con" => &mut labels.con,
+        "dup" => &mut labels.dup,
+        "tup" => &mut labels.tup,
+        _ => return Err(format!("Invalid label kind '{kind}'")),
+      };
+      let label = label.parse::<u16>().map_err(|e| format!("Invalid label '{label}': {e}"))?;
+      generator.label_to_name.insert(label, Name::new(name));
+    }
+    labels.con.finish();
+    labels.dup.finish();
+    Ok(labels)
+  }
+}
+
 impl LabelGenerator {
   // If some tag and new generate a new label, otherwise return the generated label.
   // If none use the implicit label counter.
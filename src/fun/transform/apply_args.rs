@@ -46,7 +46,8 @@ impl Ctx<'_> {
         main_body = Term::call(main_body, args);
       }
 
-      main_def.rules = vec![Rule { pats: vec![], body: main_body }];
+      let span = main_def.rules[0].span;
+      main_def.rules = vec![Rule { pats: vec![], body: main_body, span }];
     }
 
     self.info.fatal(())
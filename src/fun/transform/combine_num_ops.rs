@@ -0,0 +1,80 @@
+use crate::{
+  fun::{eval::operate_nums, Book, Num, Op, Term},
+  maybe_grow,
+};
+
+impl Book {
+  /// Combines the constants of nested numeric operations, `(op (op x a) b)`, into one, `(op x (op a b))`.
+  ///
+  /// Returns how many operations were removed.
+  ///
+  /// Numbers wrap around on overflow, so `+`, `*`, `&`, `|` and `^` are associative and commutative
+  /// for integers, and the constants can be on either side of each operation.
+  /// Both constants must be integers of the same type, since the rounding of floats
+  /// makes their operations not associative.
+  ///
+  /// This assumes that `x` is of the same type as the constants, which isn't checked.
+  /// Operations between numbers of different types return 0, so for a float `x`,
+  /// `(+ (+ x 1) 2)` is `2` but `(+ x 3)` is `0`.
+  ///
+  /// Example:
+  /// ```bend
+  /// (+ (+ x 1) 2)
+  /// (* 2 (* x 3))
+  ///
+  /// // Transforms to:
+  /// (+ x 3)
+  /// (* x 6)
+  /// ```
+  pub fn combine_num_ops(&mut self) -> usize {
+    let mut count = 0;
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        count += rule.body.combine_num_ops();
+      }
+    }
+    count
+  }
+}
+
+impl Term {
+  fn combine_num_ops(&mut self) -> usize {
+    maybe_grow(|| {
+      let mut count = self.children_mut().map(|child| child.combine_num_ops()).sum();
+
+      if let Term::Oper { opr, fst, snd } = self
+        && is_assoc_comm(*opr)
+      {
+        let (inner, b) = match (fst.as_mut(), snd.as_mut()) {
+          (inner, Term::Num { val }) | (Term::Num { val }, inner) => (inner, *val),
+          _ => return count,
+        };
+        let Term::Oper { opr: inner_opr, fst: x, snd: a } = inner else { return count };
+        if inner_opr != opr {
+          return count;
+        }
+        let (x, a) = if matches!(x.as_ref(), Term::Num { .. }) { (a, x) } else { (x, a) };
+        if let Term::Num { val: a } = a.as_ref()
+          && let Some(val) = combine_nums(*opr, *a, b)
+        {
+          let x = std::mem::take(x.as_mut());
+          *self = Term::Oper { opr: *opr, fst: Box::new(x), snd: Box::new(Term::Num { val }) };
+          count += 1;
+        }
+      }
+      count
+    })
+  }
+}
+
+fn is_assoc_comm(opr: Op) -> bool {
+  matches!(opr, Op::ADD | Op::MUL | Op::AND | Op::OR | Op::XOR)
+}
+
+/// The result of an operation on two integer constants of the same type.
+fn combine_nums(opr: Op, a: Num, b: Num) -> Option<Num> {
+  match (a, b) {
+    (Num::U24(_), Num::U24(_)) | (Num::I24(_), Num::I24(_)) => operate_nums(opr, a, b).ok(),
+    _ => None,
+  }
+}
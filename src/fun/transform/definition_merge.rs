@@ -40,8 +40,11 @@ impl Book {
       if equal_defs.len() > 1 {
         // Merging some defs
         // Add the merged def
-        let new_def =
-          Definition { name: new_name.clone(), rules: vec![Rule { pats: vec![], body: term }], builtin };
+        let new_def = Definition {
+          name: new_name.clone(),
+          rules: vec![Rule { pats: vec![], body: term, span: None }],
+          builtin,
+        };
         self.defs.insert(new_name.clone(), new_def);
         // Remove the old ones and write the map of old names to new ones.
         for name in equal_defs {
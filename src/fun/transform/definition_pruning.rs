@@ -27,22 +27,26 @@ enum Used {
 
 type Definitions = HashMap<Name, Used>;
 
+/// The definition whose body made each used definition be kept, to explain why it's used.
+type Parents = HashMap<Name, Name>;
+
 impl Ctx<'_> {
   /// If `prune_all`, removes all unused definitions and adts starting from Main.
   /// Otherwise, prunes only the builtins not accessible from any non-built-in definition
   pub fn prune(&mut self, prune_all: bool) {
     let mut used = Definitions::new();
+    let mut parents = Parents::new();
 
     if let Some(main) = &self.book.entrypoint {
       let def = self.book.defs.get(main).unwrap();
       used.insert(main.clone(), Used::Main);
-      self.book.find_used_definitions(&def.rule().body, Used::Main, &mut used);
+      self.book.find_used_definitions(main, &def.rule().body, Used::Main, &mut used, &mut parents);
     }
 
     if !prune_all {
       for def in self.book.defs.values() {
         if !def.builtin && !used.contains_key(&def.name) {
-          self.book.find_used_definitions(&def.rule().body, Used::Needed, &mut used);
+          self.book.find_used_definitions(&def.name, &def.rule().body, Used::Needed, &mut used, &mut parents);
         }
       }
     }
@@ -66,7 +70,7 @@ impl Ctx<'_> {
               _ => {}
             }
 
-            self.book.find_used_definitions(&rule.body, Used::Unused, &mut used);
+            self.book.find_used_definitions(def_name, &rule.body, Used::Unused, &mut used, &mut parents);
           }
         }
       }
@@ -95,22 +99,55 @@ impl Ctx<'_> {
 }
 
 impl Book {
+  /// Returns a chain of definitions from the entrypoint to `target`,
+  /// explaining why the definition is kept alive by pruning.
+  ///
+  /// Each definition in the chain is kept because of the previous one, either by a reference to it,
+  /// by a reference to another constructor of its type, or by a list or string literal for the builtin constructors.
+  /// Returns `None` if there's no entrypoint or if `target` is not kept because of it.
+  pub fn reachability_path(&self, target: &Name) -> Option<Vec<Name>> {
+    let main = self.entrypoint.as_ref()?;
+    let def = self.defs.get(main)?;
+
+    let mut used = Definitions::from([(main.clone(), Used::Main)]);
+    let mut parents = Parents::new();
+    self.find_used_definitions(main, &def.rule().body, Used::Main, &mut used, &mut parents);
+    if !used.contains_key(target) {
+      return None;
+    }
+
+    let mut path = vec![target.clone()];
+    while let Some(parent) = parents.get(path.last().unwrap()) {
+      path.push(parent.clone());
+    }
+    path.reverse();
+    Some(path)
+  }
+
   /// Finds all used definitions on every term that can have a def_id.
-  fn find_used_definitions(&self, term: &Term, used: Used, uses: &mut Definitions) {
+  /// `from` is the definition that `term` belongs to.
+  fn find_used_definitions(
+    &self,
+    from: &Name,
+    term: &Term,
+    used: Used,
+    uses: &mut Definitions,
+    parents: &mut Parents,
+  ) {
     maybe_grow(|| {
       let mut to_find = vec![term];
 
       while let Some(term) = to_find.pop() {
         match term {
           Term::Ref { nam: def_name } => match self.ctrs.get(def_name) {
-            Some(name) => self.insert_ctrs_used(name, uses),
-            None => self.insert_used(def_name, used, uses),
+            Some(name) => self.insert_ctrs_used(from, name, uses, parents),
+            None => self.insert_used(from, def_name, used, uses, parents),
           },
           Term::List { .. } => {
-            self.insert_ctrs_used(&Name::new(LIST), uses);
+            self.insert_ctrs_used(from, &Name::new(LIST), uses, parents);
           }
           Term::Str { .. } => {
-            self.insert_ctrs_used(&Name::new(STRING), uses);
+            self.insert_ctrs_used(from, &Name::new(STRING), uses, parents);
           }
           _ => {}
         }
@@ -122,22 +159,30 @@ impl Book {
     })
   }
 
-  fn insert_used(&self, def_name: &Name, used: Used, uses: &mut Definitions) {
+  fn insert_used(
+    &self,
+    from: &Name,
+    def_name: &Name,
+    used: Used,
+    uses: &mut Definitions,
+    parents: &mut Parents,
+  ) {
     if let Entry::Vacant(e) = uses.entry(def_name.clone()) {
       e.insert(used);
+      parents.insert(def_name.clone(), from.clone());
 
       // This needs to be done for each rule in case the pass it's ran from has not encoded the pattern match
       // E.g.: the `flatten_rules` golden test
       for rule in &self.defs[def_name].rules {
-        self.find_used_definitions(&rule.body, used, uses);
+        self.find_used_definitions(def_name, &rule.body, used, uses, parents);
       }
     }
   }
 
-  fn insert_ctrs_used(&self, name: &Name, uses: &mut Definitions) {
+  fn insert_ctrs_used(&self, from: &Name, name: &Name, uses: &mut Definitions, parents: &mut Parents) {
     if let Some(Adt { ctrs, .. }) = self.adts.get(name) {
       for (ctr, _) in ctrs {
-        self.insert_used(ctr, Used::Adt, uses);
+        self.insert_used(from, ctr, Used::Adt, uses, parents);
       }
     }
   }
@@ -1,6 +1,6 @@
 use crate::{
   diagnostics::{Diagnostics, WarningType},
-  fun::{builtins, Adts, Constructors, Ctx, Definition, FanKind, Name, Num, Pattern, Rule, Tag, Term},
+  fun::{builtins, Adts, Constructors, Ctx, Definition, FanKind, Name, Num, Pattern, Rule, Span, Tag, Term},
 };
 use std::collections::{BTreeSet, HashSet};
 
@@ -8,7 +8,7 @@ pub enum DesugarMatchDefErr {
   AdtNotExhaustive { adt: Name, ctr: Name },
   NumMissingDefault,
   TypeMismatch { expected: Type, found: Type, pat: Pattern },
-  RepeatedBind { bind: Name },
+  RepeatedBind { bind: Name, span: Option<Span> },
 }
 
 impl Ctx<'_> {
@@ -43,10 +43,12 @@ impl Definition {
 
     let args = (0 .. self.arity()).map(|i| Name::new(format!("%arg{i}"))).collect::<Vec<_>>();
     let rules = std::mem::take(&mut self.rules);
+    // A single rule is only moved into the body, so it's still where it was written.
+    let span = if let [rule] = rules.as_slice() { rule.span } else { None };
     match simplify_rule_match(args.clone(), rules, vec![], ctrs, adts) {
       Ok(body) => {
         let body = args.into_iter().rfold(body, |body, arg| Term::lam(Pattern::Var(Some(arg)), body));
-        self.rules = vec![Rule { pats: vec![], body }];
+        self.rules = vec![Rule { pats: vec![], body, span }];
       }
       Err(e) => errs.push(e),
     }
@@ -72,7 +74,7 @@ fn fix_repeated_binds(rules: &mut [Rule]) -> Vec<DesugarMatchDefErr> {
       if binds.contains(nam) {
         // Repeated bind, not reachable and can be erased.
         if let Some(nam) = nam {
-          errs.push(DesugarMatchDefErr::RepeatedBind { bind: nam.clone() });
+          errs.push(DesugarMatchDefErr::RepeatedBind { bind: nam.clone(), span: rule.span });
         }
         *nam = None;
         // TODO: Send a repeated bind warning
@@ -161,7 +163,7 @@ fn var_rule(
       };
     }
 
-    let new_rule = Rule { pats: new_pats, body: rule.body };
+    let new_rule = Rule { pats: new_pats, body: rule.body, span: rule.span };
     new_rules.push(new_rule);
   }
 
@@ -222,7 +224,7 @@ fn fan_rule(
     };
     new_pats.extend(old_pats);
 
-    let new_rule = Rule { pats: new_pats, body: rule.body };
+    let new_rule = Rule { pats: new_pats, body: rule.body, span: rule.span };
     new_rules.push(new_rule);
   }
 
@@ -272,7 +274,7 @@ fn num_rule(
       match &rule.pats[0] {
         Pattern::Num(n) if n == num => {
           let body = rule.body.clone();
-          let rule = Rule { pats: rule.pats[1 ..].to_vec(), body };
+          let rule = Rule { pats: rule.pats[1 ..].to_vec(), body, span: rule.span };
           new_rules.push(rule);
         }
         Pattern::Var(var) => {
@@ -284,7 +286,7 @@ fn num_rule(
               nxt: Box::new(std::mem::take(&mut body)),
             };
           }
-          let rule = Rule { pats: rule.pats[1 ..].to_vec(), body };
+          let rule = Rule { pats: rule.pats[1 ..].to_vec(), body, span: rule.span };
           new_rules.push(rule);
         }
         _ => (),
@@ -304,7 +306,7 @@ fn num_rule(
         let var_recovered = Term::add_num(Term::Var { nam: pred_var.clone() }, Num::U24(1 + last_num));
         body = Term::Use { nam: Some(var.clone()), val: Box::new(var_recovered), nxt: Box::new(body) };
       }
-      let rule = Rule { pats: rule.pats[1 ..].to_vec(), body };
+      let rule = Rule { pats: rule.pats[1 ..].to_vec(), body, span: rule.span };
       new_rules.push(rule);
     }
   }
@@ -414,7 +416,7 @@ fn switch_rule(
         Pattern::Ctr(found_ctr, new_pats) if ctr == found_ctr => {
           let pats = new_pats.iter().cloned().chain(old_pats).collect();
           let body = rule.body.clone();
-          let rule = Rule { pats, body };
+          let rule = Rule { pats, body, span: rule.span };
           new_rules.push(rule);
         }
         // Var, match and rebuild the constructor.
@@ -432,7 +434,7 @@ fn switch_rule(
             body =
               Term::Use { nam: Some(var.clone()), val: Box::new(reconstructed_var), nxt: Box::new(body) };
           }
-          let rule = Rule { pats, body };
+          let rule = Rule { pats, body, span: rule.span };
           new_rules.push(rule);
         }
         _ => (),
@@ -541,7 +543,10 @@ impl std::fmt::Display for DesugarMatchDefErr {
       DesugarMatchDefErr::NumMissingDefault => {
         write!(f, "Non-exhaustive pattern matching rule. Default case of number type not covered.")
       }
-      DesugarMatchDefErr::RepeatedBind { bind } => {
+      DesugarMatchDefErr::RepeatedBind { bind, span: Some(span) } => {
+        write!(f, "Repeated bind in pattern matching rule at {span}: '{bind}'.")
+      }
+      DesugarMatchDefErr::RepeatedBind { bind, span: None } => {
         write!(f, "Repeated bind in pattern matching rule: '{bind}'.")
       }
     }
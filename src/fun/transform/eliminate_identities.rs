@@ -0,0 +1,89 @@
+use crate::{
+  fun::{Book, Name, Pattern, Tag, Term},
+  maybe_grow,
+};
+use std::collections::HashSet;
+
+impl Book {
+  /// Removes the applications of identity lambdas, `(λx x arg)`, replacing them by their argument,
+  /// and the definitions that are just an identity lambda, inlining their references.
+  ///
+  /// Returns how many applications and references were eliminated.
+  ///
+  /// The entrypoint, builtins and constructors are never removed.
+  /// Definitions that only become identities after the simplification are also removed.
+  ///
+  /// Example:
+  /// ```bend
+  /// id = λx x
+  /// main = (Foo (id 1) (λy y 2) id)
+  ///
+  /// // Transforms to:
+  /// main = (Foo 1 2 λx x)
+  /// ```
+  pub fn eliminate_identities(&mut self) -> usize {
+    let mut count = 0;
+    loop {
+      let ids = self
+        .defs
+        .values()
+        .filter(|def| {
+          !def.builtin
+            && !self.ctrs.contains_key(&def.name)
+            && self.entrypoint.as_ref() != Some(&def.name)
+            && matches!(def.rules.as_slice(), [rule] if rule.pats.is_empty() && rule.body.is_identity())
+        })
+        .map(|def| def.name.clone())
+        .collect::<HashSet<_>>();
+      if ids.is_empty() {
+        return count;
+      }
+
+      self.defs.retain(|def_name, _| !ids.contains(def_name));
+      for def in self.defs.values_mut() {
+        for rule in def.rules.iter_mut() {
+          count += rule.body.eliminate_identities(&ids);
+        }
+      }
+    }
+  }
+}
+
+impl Term {
+  fn eliminate_identities(&mut self, ids: &HashSet<Name>) -> usize {
+    maybe_grow(|| {
+      let mut count = 0;
+      match self {
+        Term::App { tag: Tag::Static, fun, arg } => {
+          let is_id_ref = matches!(fun.as_ref(), Term::Ref { nam } if ids.contains(nam));
+          if !is_id_ref {
+            count += fun.eliminate_identities(ids);
+          }
+          count += arg.eliminate_identities(ids);
+          if is_id_ref || fun.is_identity() {
+            *self = std::mem::take(arg.as_mut());
+            count += 1;
+          }
+        }
+        Term::Ref { nam } if ids.contains(nam) => {
+          *self = Term::lam(Pattern::Var(Some(Name::new("x"))), Term::Var { nam: Name::new("x") });
+          count += 1;
+        }
+        _ => {
+          for child in self.children_mut() {
+            count += child.eliminate_identities(ids);
+          }
+        }
+      }
+      count
+    })
+  }
+
+  /// Whether this term is an untagged lambda that returns its argument, `λx x`.
+  fn is_identity(&self) -> bool {
+    matches!(
+      self,
+      Term::Lam { tag: Tag::Static, pat: box Pattern::Var(Some(bnd)), bod: box Term::Var { nam } } if bnd == nam
+    )
+  }
+}
@@ -1,7 +1,8 @@
 use crate::{
-  diagnostics::Diagnostics,
-  fun::{Adts, Constructors, Ctx, Pattern},
+  diagnostics::{Diagnostics, ERR_INDENT_SIZE},
+  fun::{Adts, Constructors, Ctx, Definition, Pattern},
 };
+use std::fmt::Write;
 
 impl Ctx<'_> {
   /// Makes every pattern matching definition have correct a left-hand side.
@@ -14,15 +15,11 @@ impl Ctx<'_> {
       let mut errs = vec![];
 
       let def_arity = def.arity();
-      for rule in &mut def.rules {
-        if rule.arity() != def_arity {
-          errs.push(format!(
-            "Incorrect pattern matching rule arity. Expected {} args, found {}.",
-            def_arity,
-            rule.arity()
-          ));
-        }
+      if def.rules.iter().any(|rule| rule.arity() != def_arity) {
+        errs.push(rule_arities_err(def));
+      }
 
+      for rule in &mut def.rules {
         for pat in &mut rule.pats {
           pat.resolve_pat(&self.book.ctrs);
           pat.check_good_ctr(&self.book.ctrs, &self.book.adts, &mut errs);
@@ -38,6 +35,32 @@ impl Ctx<'_> {
   }
 }
 
+/// The error for a definition whose rules don't all have the same arity as the first,
+/// listing every rule with its arity and marking the ones that differ.
+fn rule_arities_err(def: &Definition) -> String {
+  let def_arity = def.arity();
+  let mut err = format!(
+    "Incorrect pattern matching rule arity. Expected every rule to have {def_arity} args, like the first one:"
+  );
+  for (i, rule) in def.rules.iter().enumerate() {
+    let args = if rule.arity() == 1 { "arg" } else { "args" };
+    let (indent, num, arity) = (2 * ERR_INDENT_SIZE, i + 1, rule.arity());
+    write!(err, "\n{:indent$}Rule {num}", "").unwrap();
+    if let Some(span) = rule.span {
+      write!(err, " at {span}").unwrap();
+    }
+    write!(err, ": ({}", def.name).unwrap();
+    for pat in &rule.pats {
+      write!(err, " {pat}").unwrap();
+    }
+    write!(err, ") has {arity} {args}").unwrap();
+    if arity != def_arity {
+      write!(err, " \x1b[1m\x1b[31m<- expected {def_arity}\x1b[0m").unwrap();
+    }
+  }
+  err
+}
+
 impl Pattern {
   /// If a var pattern actually refers to an ADT constructor, convert it into a constructor pattern.
   fn resolve_pat(&mut self, ctrs: &Constructors) {
@@ -53,7 +76,7 @@ impl Pattern {
 
   /// Check that ADT constructor pats are correct, meaning defined in a `data` and with correct arity.
   fn check_good_ctr(&self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<String>) {
-    if let Pattern::Ctr(nam, args) = self {
+    if let Some((nam, args)) = self.as_ctr() {
       if let Some(adt) = ctrs.get(nam) {
         let expected_arity = adts[adt].ctrs[nam].len();
         let found_arity = args.len();
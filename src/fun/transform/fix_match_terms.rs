@@ -1,8 +1,9 @@
 use crate::{
   diagnostics::{Diagnostics, WarningType, ERR_INDENT_SIZE},
-  fun::{Adts, Constructors, CtrField, Ctx, MatchRule, Name, Num, Term},
+  fun::{Adts, Constructors, CtrField, Ctx, FanKind, MatchRule, Name, Num, Pattern, Term},
   maybe_grow,
 };
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
 enum FixMatchErr {
@@ -11,6 +12,19 @@ enum FixMatchErr {
   IrrefutableMatch { var: Option<Name> },
   UnreachableMatchArms { var: Option<Name> },
   RedundantArm { ctr: Name },
+  GuardWithoutFallback { arm: Option<Name> },
+  NonAdtMatch { typ: NonAdtType },
+}
+
+/// The type of a matched value that is clearly not an ADT.
+enum NonAdtType {
+  U24,
+  I24,
+  F24,
+  Number,
+  Function,
+  Tuple,
+  Superposition,
 }
 
 impl Ctx<'_> {
@@ -19,6 +33,8 @@ impl Ctx<'_> {
   /// * For switches, resolve the succ case ("_") and create the name of the pred variable.
   /// * If the match arg is not a variable, it is separated into a let expression and bound to "%matched"
   /// * Check for redundant arms and non-exhaustive matches.
+  /// * Fill the guarded arms with the arm to fall back to when their guard fails,
+  ///   which is the next one that matches the same constructor.
   ///
   /// Example:
   /// For the program
@@ -50,9 +66,10 @@ impl Ctx<'_> {
 
         for err in errs {
           match err {
-            FixMatchErr::AdtMismatch { .. } | FixMatchErr::NonExhaustiveMatch { .. } => {
-              self.info.add_rule_error(err, def.name.clone())
-            }
+            FixMatchErr::AdtMismatch { .. }
+            | FixMatchErr::NonExhaustiveMatch { .. }
+            | FixMatchErr::GuardWithoutFallback { .. }
+            | FixMatchErr::NonAdtMatch { .. } => self.info.add_rule_error(err, def.name.clone()),
             FixMatchErr::IrrefutableMatch { .. } => {
               self.info.add_rule_warning(err, WarningType::IrrefutableMatch, def.name.clone())
             }
@@ -76,13 +93,26 @@ impl Term {
     maybe_grow(|| {
       let mut errs = Vec::new();
 
-      for child in self.children_mut() {
+      // Take out the guards first, so that they're not fixed like regular switches.
+      let mut guards = match self {
+        Term::Mat { arms, .. } | Term::Fold { arms, .. } => {
+          arms.iter_mut().map(|arm| arm.2.take_guard()).collect::<Vec<_>>()
+        }
+        _ => vec![],
+      };
+
+      for child in self.children_mut().chain(guards.iter_mut().flatten()) {
         let mut e = child.fix_match_terms(ctrs, adts);
         errs.append(&mut e);
       }
 
-      if matches!(self, Term::Mat { .. } | Term::Fold { .. }) {
-        self.fix_match(&mut errs, ctrs, adts);
+      if let Term::Mat { arg, arms, .. } | Term::Fold { arg, arms, .. } = self
+        && let Some(typ) = non_adt_match_type(arg, arms, ctrs)
+      {
+        // Fixing the arms would only give more confusing errors about the same mistake.
+        errs.push(FixMatchErr::NonAdtMatch { typ });
+      } else if matches!(self, Term::Mat { .. } | Term::Fold { .. }) {
+        self.fix_match(guards, &mut errs, ctrs, adts);
       }
       // Add a use term to each arm rebuilding the matched variable
       match self {
@@ -124,20 +154,28 @@ impl Term {
     })
   }
 
-  fn fix_match(&mut self, errs: &mut Vec<FixMatchErr>, ctrs: &Constructors, adts: &Adts) {
+  fn fix_match(
+    &mut self,
+    mut guards: Vec<Option<Term>>,
+    errs: &mut Vec<FixMatchErr>,
+    ctrs: &Constructors,
+    adts: &Adts,
+  ) {
     let (Term::Mat { arg: _, bnd, with: _, arms } | Term::Fold { bnd, arg: _, with: _, arms }) = self else {
       unreachable!()
     };
     let bnd = bnd.clone().unwrap();
 
+    // The arms that are tried before the first one without a guard.
+    let first_arms = guards.iter().position(Option::is_none).map_or(arms.len(), |i| i + 1);
+
     // Normalize arms, making one arm for each constructor of the matched adt.
-    if let Some(ctr_nam) = &arms[0].0
-      && let Some(adt_nam) = ctrs.get(ctr_nam)
-    {
-      let adt_ctrs = &adts[adt_nam].ctrs;
+    if let Some(adt_nam) = arms[.. first_arms].iter().find_map(|arm| ctrs.get(arm.0.as_ref()?)) {
+      let adt_nam = adt_nam.clone();
+      let adt_ctrs = &adts[&adt_nam].ctrs;
 
       // Decide which constructor corresponds to which arm of the match.
-      let mut bodies = fixed_match_arms(&bnd, arms, adt_nam, adt_ctrs.keys(), ctrs, adts, errs);
+      let mut bodies = fixed_match_arms(&bnd, arms, guards, &adt_nam, adt_ctrs.keys(), ctrs, adts, errs);
 
       // Build the match arms, with all constructors
       let mut new_rules = vec![];
@@ -154,45 +192,114 @@ impl Term {
       *arms = new_rules;
     } else {
       // First arm was not matching a constructor, convert into a use term.
-      errs.push(FixMatchErr::IrrefutableMatch { var: arms[0].0.clone() });
-      let match_var = arms[0].0.take();
-      *self = std::mem::take(&mut arms[0].2);
-      if let Some(var) = match_var {
-        *self = Term::Use {
-          nam: Some(var),
-          val: Box::new(Term::Var { nam: bnd }),
-          nxt: Box::new(std::mem::take(self)),
+      // If it has a guard, fall back to the next arms until one without a guard.
+      errs.push(FixMatchErr::IrrefutableMatch { var: arms[first_arms - 1].0.clone() });
+      if guards[first_arms - 1].is_some() {
+        errs.push(FixMatchErr::GuardWithoutFallback { arm: arms[first_arms - 1].0.clone() });
+      }
+      guards.truncate(first_arms);
+      let mut body = Term::Err;
+      for ((match_var, _, arm_body), guard) in arms.drain(.. first_arms).zip(guards).rev() {
+        let bind = |term| match &match_var {
+          Some(var) => Term::Use {
+            nam: Some(var.clone()),
+            val: Box::new(Term::Var { nam: bnd.clone() }),
+            nxt: Box::new(term),
+          },
+          None => term,
         };
+        body = guarded_body(guard.map(bind), body, bind(arm_body));
       }
+      // The matched value is still bound to the name of the match.
+      let (Term::Mat { arg, .. } | Term::Fold { arg, .. }) = self else { unreachable!() };
+      if !matches!(arg.as_ref(), Term::Var { nam } if *nam == bnd) {
+        let pat = Box::new(Pattern::Var(Some(bnd)));
+        body = Term::Let { pat, val: Box::new(std::mem::take(arg)), nxt: Box::new(body) };
+      }
+      *self = body;
+    }
+  }
+}
+
+/// The type of the value of a match, if it's clearly not an ADT.
+///
+/// Only the values written directly as the argument of the match are known,
+/// plus the numbers implied by arms that look like the cases of a switch.
+/// Matches whose arms are all variables are irrefutable, not wrong, so they're left alone.
+fn non_adt_match_type(arg: &Term, arms: &[MatchRule], ctrs: &Constructors) -> Option<NonAdtType> {
+  let is_num_arm = |arm: &MatchRule| arm.0.as_ref().is_some_and(|nam| nam.parse::<u32>().is_ok());
+  let is_ctr_arm = |arm: &MatchRule| arm.0.as_ref().is_some_and(|nam| ctrs.contains_key(nam));
+  if arms.iter().any(is_num_arm) {
+    return Some(non_adt_type(arg).unwrap_or(NonAdtType::U24));
+  }
+  if arms.iter().any(is_ctr_arm) {
+    return non_adt_type(arg);
+  }
+  None
+}
+
+/// The type of a term, if it's clearly not an ADT.
+fn non_adt_type(term: &Term) -> Option<NonAdtType> {
+  match term {
+    Term::Num { val: Num::U24(_) } => Some(NonAdtType::U24),
+    Term::Num { val: Num::I24(_) } => Some(NonAdtType::I24),
+    Term::Num { val: Num::F24(_) } => Some(NonAdtType::F24),
+    Term::Oper { .. } => Some(NonAdtType::Number),
+    Term::Lam { .. } => Some(NonAdtType::Function),
+    Term::Fan { fan: FanKind::Tup, .. } => Some(NonAdtType::Tuple),
+    Term::Fan { fan: FanKind::Dup, .. } => Some(NonAdtType::Superposition),
+    _ => None,
+  }
+}
+
+/// The body of an arm with an optional guard, that goes to `fallback` if the guard fails.
+fn guarded_body(guard: Option<Term>, fallback: Term, body: Term) -> Term {
+  if let Some(guard) = guard {
+    Term::Swt {
+      arg: Box::new(guard),
+      bnd: None,
+      with: vec![],
+      pred: Some(Name::new("%guard-1")),
+      arms: vec![fallback, body],
     }
+  } else {
+    body
   }
 }
 
 /// Given the rules of a match term, return the bodies that match
 /// each of the constructors of the matched ADT.
 ///
+/// A rule with a guard falls back to the next rule that matches the same constructor.
+///
 /// If no rules match a certain constructor, return None in the map,
 /// indicating a non-exhaustive match.
+#[allow(clippy::too_many_arguments)]
 fn fixed_match_arms<'a>(
   bnd: &Name,
   rules: &mut Vec<MatchRule>,
+  guards: Vec<Option<Term>>,
   adt_nam: &Name,
   adt_ctrs: impl Iterator<Item = &'a Name>,
   ctrs: &Constructors,
   adts: &Adts,
   errs: &mut Vec<FixMatchErr>,
 ) -> HashMap<&'a Name, Option<Term>> {
-  let mut bodies = HashMap::<&Name, Option<Term>>::from_iter(adt_ctrs.map(|ctr| (ctr, None)));
-  for rule_idx in 0 .. rules.len() {
+  // The guards and bodies of the rules that match each constructor, up to the first one without a guard.
+  let mut matched = IndexMap::<&Name, (Vec<(Option<Term>, Term)>, bool)>::from_iter(
+    adt_ctrs.map(|ctr| (ctr, (vec![], false))),
+  );
+  for (rule_idx, guard) in guards.into_iter().enumerate() {
     if let Some(ctr_nam) = &rules[rule_idx].0
       && let Some(found_adt) = ctrs.get(ctr_nam)
     {
       // Ctr arm, use the body of this rule for this constructor.
       if found_adt == adt_nam {
-        let body = bodies.get_mut(ctr_nam).unwrap();
-        if body.is_none() {
+        let (bodies, complete) = matched.get_mut(ctr_nam).unwrap();
+        if !*complete {
           // Use this rule for this constructor
-          *body = Some(rules[rule_idx].2.clone());
+          *complete = guard.is_none();
+          bodies.push((guard, rules[rule_idx].2.clone()));
         } else {
           errs.push(FixMatchErr::RedundantArm { ctr: ctr_nam.clone() });
         }
@@ -205,28 +312,43 @@ fn fixed_match_arms<'a>(
       }
     } else {
       // Var arm, use the body of this rule for all non-covered constructors.
-      for (ctr, body) in bodies.iter_mut() {
-        if body.is_none() {
-          let mut new_body = rules[rule_idx].2.clone();
-          if let Some(var) = &rules[rule_idx].0 {
-            new_body = Term::Use {
+      let is_last = guard.is_none();
+      for (ctr, (bodies, complete)) in matched.iter_mut() {
+        if !*complete {
+          let bind = |term| match &rules[rule_idx].0 {
+            Some(var) => Term::Use {
               nam: Some(var.clone()),
               val: Box::new(rebuild_ctr(bnd, ctr, &adts[adt_nam].ctrs[&**ctr])),
-              nxt: Box::new(new_body),
-            };
-          }
-          *body = Some(new_body);
+              nxt: Box::new(term),
+            },
+            None => term,
+          };
+          *complete = is_last;
+          bodies.push((guard.clone().map(bind), bind(rules[rule_idx].2.clone())));
         }
       }
 
-      if rule_idx != rules.len() - 1 {
-        errs.push(FixMatchErr::UnreachableMatchArms { var: rules[rule_idx].0.clone() });
-        rules.truncate(rule_idx + 1);
+      if is_last {
+        if rule_idx != rules.len() - 1 {
+          errs.push(FixMatchErr::UnreachableMatchArms { var: rules[rule_idx].0.clone() });
+          rules.truncate(rule_idx + 1);
+        }
+        break;
       }
-      break;
     }
   }
-  bodies
+
+  let mut fixed = HashMap::new();
+  for (ctr, (bodies, complete)) in matched {
+    if !bodies.is_empty() && !complete {
+      errs.push(FixMatchErr::GuardWithoutFallback { arm: Some(ctr.clone()) });
+    }
+    let body = (!bodies.is_empty()).then(|| {
+      bodies.into_iter().rfold(Term::Err, |fallback, (guard, body)| guarded_body(guard, fallback, body))
+    });
+    fixed.insert(ctr, body);
+  }
+  fixed
 }
 
 fn match_field(arg: &Name, field: &Name) -> Name {
@@ -275,6 +397,45 @@ impl std::fmt::Display for FixMatchErr {
       FixMatchErr::RedundantArm { ctr } => {
         write!(f, "Redundant arm in 'match' expression. Case '{ctr}' appears more than once.")
       }
+      FixMatchErr::GuardWithoutFallback { arm } => write!(
+        f,
+        "Non-exhaustive 'match' expression. Case '{}' only has arms with a guard, and needs one without a guard to fall back to.",
+        arm.as_ref().unwrap_or(&Name::new("*"))
+      ),
+      FixMatchErr::NonAdtMatch { typ } => {
+        writeln!(f, "Cannot match on non-ADT value of type '{typ}'.")?;
+        match typ {
+          NonAdtType::U24 | NonAdtType::I24 | NonAdtType::F24 | NonAdtType::Number => {
+            write!(f, "{:ERR_INDENT_SIZE$}To match on a number, use a 'switch' expression instead.", "")
+          }
+          NonAdtType::Tuple | NonAdtType::Superposition => {
+            write!(
+              f,
+              "{:ERR_INDENT_SIZE$}To get the elements of a {typ}, use a 'let' expression instead.",
+              ""
+            )
+          }
+          NonAdtType::Function => write!(
+            f,
+            "{:ERR_INDENT_SIZE$}Only the constructors of a type declared with 'data' can be matched on.",
+            ""
+          ),
+        }
+      }
+    }
+  }
+}
+
+impl std::fmt::Display for NonAdtType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      NonAdtType::U24 => write!(f, "u24"),
+      NonAdtType::I24 => write!(f, "i24"),
+      NonAdtType::F24 => write!(f, "f24"),
+      NonAdtType::Number => write!(f, "number"),
+      NonAdtType::Function => write!(f, "function"),
+      NonAdtType::Tuple => write!(f, "tuple"),
+      NonAdtType::Superposition => write!(f, "superposition"),
     }
   }
 }
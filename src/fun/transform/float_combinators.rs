@@ -107,7 +107,7 @@ impl Term {
     let comb_ref = Term::Ref { nam: comb_name.clone() };
     let extracted_term = std::mem::replace(self, comb_ref);
 
-    let rules = vec![Rule { body: extracted_term, pats: Vec::new() }];
+    let rules = vec![Rule { body: extracted_term, pats: Vec::new(), span: None }];
     let rule = Definition { name: comb_name.clone(), rules, builtin };
     ctx.combinators.insert(comb_name, (is_safe, rule));
   }
@@ -214,7 +214,8 @@ impl Term {
     }
   }
 
-  fn size(&self) -> usize {
+  /// The number of nodes this term compiles to.
+  pub fn size(&self) -> usize {
     maybe_grow(|| {
       let children_size: usize = self.children().map(|c| c.size()).sum();
       self.base_size() + children_size
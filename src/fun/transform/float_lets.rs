@@ -0,0 +1,112 @@
+use crate::{
+  fun::{Book, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::HashSet;
+
+impl Book {
+  /// Moves the `let`s that start every arm of a match with the same value to before the match,
+  /// so that the value is built only once in the code instead of once per arm.
+  ///
+  /// Returns how many `let`s were floated.
+  ///
+  /// A `let` is only floated if its value doesn't use the matched variable or the fields bound by the arm,
+  /// has no unscoped variables, and its variable is used in every arm,
+  /// so that the value isn't computed when the chosen arm wouldn't need it.
+  ///
+  /// Must be called after `fix_match_terms` and `make_var_names_unique`,
+  /// since the variables of the other arms are renamed to the one of the first.
+  /// The `use`s at the start of the arms are skipped.
+  ///
+  /// Example:
+  /// ```bend
+  /// match x { Bool/T: let y = (Foo z); (A y); Bool/F: let w = (Foo z); (B w) }
+  ///
+  /// // Transforms to:
+  /// let y = (Foo z); match x { Bool/T: (A y); Bool/F: (B y) }
+  /// ```
+  pub fn float_lets(&mut self) -> usize {
+    let mut count = 0;
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        count += rule.body.float_lets();
+      }
+    }
+    count
+  }
+}
+
+impl Term {
+  fn float_lets(&mut self) -> usize {
+    maybe_grow(|| {
+      // After floating, the match is the body of the new `let`,
+      // so the recursion floats the next common `let` of its arms.
+      let floated = self.float_common_let() as usize;
+      floated + self.children_mut().map(Term::float_lets).sum::<usize>()
+    })
+  }
+
+  /// If this is a match whose arms all start with the same floatable `let`, moves it before the match.
+  fn float_common_let(&mut self) -> bool {
+    let (mut binds, arms) = match self {
+      Term::Mat { bnd, arms, .. } => {
+        let binds = bnd.iter().chain(arms.iter().flat_map(|(_, fields, _)| fields.iter().flatten()));
+        (binds.cloned().collect::<HashSet<_>>(), arms.iter_mut().map(|(_, _, bod)| bod).collect::<Vec<_>>())
+      }
+      Term::Swt { bnd, pred, arms, .. } => {
+        let binds = bnd.iter().chain(pred.iter()).cloned().collect::<HashSet<_>>();
+        (binds, arms.iter_mut().collect::<Vec<_>>())
+      }
+      _ => return false,
+    };
+    if arms.len() < 2 {
+      return false;
+    }
+    // `fix_match_terms` starts the arms with `use`s of the matched constructor, which the `let`s can't depend on.
+    let mut arms = arms.into_iter().map(|arm| skip_uses(arm, &mut binds)).collect::<Vec<_>>();
+
+    let Term::Let { pat: box Pattern::Var(Some(nam)), val, .. } = &*arms[0] else { return false };
+    let (declared, used) = val.unscoped_vars();
+    if !declared.is_empty() || !used.is_empty() || val.free_vars().keys().any(|var| binds.contains(var)) {
+      return false;
+    }
+    let is_common = |arm: &Term| match arm {
+      Term::Let { pat: box Pattern::Var(Some(arm_nam)), val: arm_val, nxt } => {
+        arm_val == val && nxt.free_vars().contains_key(arm_nam)
+      }
+      _ => false,
+    };
+    if !arms.iter().all(|arm| is_common(arm)) {
+      return false;
+    }
+
+    let nam = nam.clone();
+    let mut val = None;
+    for arm in arms.iter_mut() {
+      let Term::Let { pat: box Pattern::Var(Some(arm_nam)), val: arm_val, nxt } = &mut **arm else {
+        unreachable!()
+      };
+      let arm_nam = std::mem::take(arm_nam);
+      let arm_val = std::mem::take(arm_val);
+      **arm = std::mem::take(nxt.as_mut());
+      if arm_nam != nam {
+        arm.subst(&arm_nam, &Term::Var { nam: nam.clone() });
+      }
+      val.get_or_insert(arm_val);
+    }
+
+    let mat = std::mem::take(self);
+    *self = Term::Let { pat: Box::new(Pattern::Var(Some(nam))), val: val.unwrap(), nxt: Box::new(mat) };
+    true
+  }
+}
+
+fn skip_uses<'a>(term: &'a mut Term, binds: &mut HashSet<Name>) -> &'a mut Term {
+  match term {
+    Term::Use { nam, nxt, .. } => {
+      binds.extend(nam.clone());
+      skip_uses(nxt, binds)
+    }
+    _ => term,
+  }
+}
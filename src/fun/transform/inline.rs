@@ -0,0 +1,209 @@
+use crate::{
+  fun::{Book, FanKind, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::{HashMap, HashSet};
+
+impl Book {
+  /// Replaces references to small definitions with their bodies.
+  ///
+  /// Only definitions that compile to at most `budget` nodes are inlined,
+  /// so that small helpers don't cost a reference expansion but the code size stays in check.
+  /// Definitions that are or can reach a recursive definition, constructors,
+  /// the entrypoint and definitions with unscoped variables are never inlined.
+  /// Moving references to recursive definitions around could undo `float_combinators`.
+  /// Neither are bodies with lambdas or duplications, since each copy ends up where a
+  /// reference was, which may itself be duplicated, and copying those changes how they share work.
+  ///
+  /// Must be called after `linearize_vars` and followed by `make_var_names_unique`,
+  /// since each inlined copy repeats the variable names of the definition.
+  pub fn inline_small_definitions(&mut self, budget: usize) {
+    let recursive = self.reaching_recursion();
+    let mut inlinees = HashMap::new();
+    for (def_name, def) in &self.defs {
+      if def.rules.len() == 1 && def.rule().body.size() <= budget && self.can_inline(def_name, &recursive) {
+        inlinees.insert(def_name.clone(), def.rule().body.clone());
+      }
+    }
+
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.inline_refs(&inlinees);
+      }
+    }
+  }
+
+  /// Replaces the only reference to each definition referenced exactly once by its body,
+  /// removing the inlined definitions.
+  ///
+  /// Returns how many definitions were inlined.
+  ///
+  /// Since the code is moved instead of copied, this never grows the program.
+  /// The same definitions as in [`Book::inline_small_definitions`] are never inlined,
+  /// and neither are the ones that would make the definition they end up in larger than `max_size` nodes,
+  /// which would undo `float_combinators`.
+  ///
+  /// Must be called after `linearize_vars`.
+  pub fn inline_single_use(&mut self, max_size: usize) -> usize {
+    let mut uses = HashMap::<&Name, usize>::new();
+    let mut users = HashMap::<&Name, &Name>::new();
+    for (def_name, def) in &self.defs {
+      for rule in &def.rules {
+        rule.body.for_each_ref(&mut |nam| {
+          *uses.entry(nam).or_default() += 1;
+          users.insert(nam, def_name);
+        });
+      }
+    }
+
+    let recursive = self.reaching_recursion();
+    let mut sizes = self
+      .defs
+      .iter()
+      .map(|(def_name, def)| (def_name, def.rules.iter().map(|rule| rule.body.size()).sum::<usize>()))
+      .collect::<HashMap<_, _>>();
+    // The definition that each inlined one is moved into, possibly through other inlined ones.
+    let mut hosts = HashMap::<&Name, &Name>::new();
+    for (def_name, def) in &self.defs {
+      if def.rules.len() != 1 || uses.get(def_name) != Some(&1) || !self.can_inline(def_name, &recursive) {
+        continue;
+      }
+      let mut host = users[def_name];
+      while let Some(next) = hosts.get(host) {
+        host = next;
+      }
+      if sizes[host] + sizes[def_name] <= max_size {
+        *sizes.get_mut(host).unwrap() += sizes[def_name];
+        hosts.insert(def_name, host);
+      }
+    }
+    let names = hosts.into_keys().cloned().collect::<Vec<_>>();
+
+    let mut inlinees = HashMap::new();
+    for def_name in names {
+      let mut def = self.defs.shift_remove(&def_name).unwrap();
+      inlinees.insert(def_name, std::mem::take(&mut def.rule_mut().body));
+    }
+    let count = inlinees.len();
+
+    // Each inlined definition is used in either a remaining definition or another inlined one,
+    // which is in turn moved into its use, so every body is moved exactly once.
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.move_refs(&mut inlinees);
+      }
+    }
+    count
+  }
+
+  /// Whether the references to a single rule definition can be replaced by its body.
+  fn can_inline(&self, def_name: &Name, recursive: &HashSet<&Name>) -> bool {
+    let body = &self.defs[def_name].rule().body;
+    let (declared, used) = body.unscoped_vars();
+    !body.has_lam_or_dup()
+      && !self.ctrs.contains_key(def_name)
+      && self.entrypoint.as_ref() != Some(def_name)
+      && !recursive.contains(def_name)
+      && declared.is_empty()
+      && used.is_empty()
+  }
+
+  /// The definitions that are recursive or that can reach a recursive definition.
+  fn reaching_recursion(&self) -> HashSet<&Name> {
+    fn visit<'a>(
+      def_name: &'a Name,
+      book: &'a Book,
+      visiting: &mut HashSet<&'a Name>,
+      visited: &mut HashSet<&'a Name>,
+      recursive: &mut HashSet<&'a Name>,
+    ) -> bool {
+      maybe_grow(|| {
+        if visiting.contains(def_name) {
+          return true;
+        }
+        if !visited.insert(def_name) {
+          return recursive.contains(def_name);
+        }
+        let Some(def) = book.defs.get(def_name) else { return false };
+
+        visiting.insert(def_name);
+        let mut refs = vec![];
+        for rule in &def.rules {
+          rule.body.for_each_ref(&mut |nam| refs.push(nam));
+        }
+        let mut is_recursive = false;
+        for nam in refs {
+          is_recursive |= visit(nam, book, visiting, visited, recursive);
+        }
+        visiting.remove(def_name);
+
+        if is_recursive {
+          recursive.insert(def_name);
+        }
+        is_recursive
+      })
+    }
+
+    let mut visited = HashSet::new();
+    let mut recursive = HashSet::new();
+    for def_name in self.defs.keys() {
+      visit(def_name, self, &mut HashSet::new(), &mut visited, &mut recursive);
+    }
+    recursive
+  }
+}
+
+impl Term {
+  fn has_lam_or_dup(&self) -> bool {
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      match term {
+        Term::Lam { .. } | Term::Fan { fan: FanKind::Dup, .. } => return true,
+        Term::Let { pat: box Pattern::Fan(FanKind::Dup, ..), .. } => return true,
+        _ => to_visit.extend(term.children()),
+      }
+    }
+    false
+  }
+
+  fn for_each_ref<'a>(&'a self, f: &mut impl FnMut(&'a Name)) {
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      if let Term::Ref { nam } = term {
+        f(nam);
+      }
+      to_visit.extend(term.children());
+    }
+  }
+
+  fn move_refs(&mut self, inlinees: &mut HashMap<Name, Term>) {
+    maybe_grow(|| {
+      if let Term::Ref { nam } = self
+        && let Some(body) = inlinees.remove(nam)
+      {
+        *self = body;
+        self.move_refs(inlinees);
+      } else {
+        for child in self.children_mut() {
+          child.move_refs(inlinees);
+        }
+      }
+    })
+  }
+
+  fn inline_refs(&mut self, inlinees: &HashMap<Name, Term>) {
+    maybe_grow(|| {
+      if let Term::Ref { nam } = self
+        && let Some(body) = inlinees.get(nam)
+      {
+        // Inlined bodies can have references to other inlined definitions.
+        *self = body.clone();
+        self.inline_refs(inlinees);
+      } else {
+        for child in self.children_mut() {
+          child.inline_refs(inlinees);
+        }
+      }
+    })
+  }
+}
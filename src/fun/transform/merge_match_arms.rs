@@ -0,0 +1,136 @@
+use crate::{
+  fun::{Book, Definition, Name, Pattern, Rule, Term},
+  maybe_grow,
+};
+
+impl Book {
+  /// Factors the arms of a `match` that have the same body, up to the renaming of bound variables,
+  /// into a new definition that each of those arms calls.
+  ///
+  /// The new definition takes the free variables of the body, followed by the fields of the matched constructor.
+  /// Arms that use their fields differently are not merged, and neither are arms with unscoped variables.
+  ///
+  /// Returns how many arms were replaced by a call.
+  ///
+  /// Example:
+  /// ```bend
+  /// data Op = (Add a b) | (Sub a b) | (Neg a)
+  /// foo = λx λop match op {
+  ///   Op/Add: (Foo x op.a op.b)
+  ///   Op/Sub: (Foo x op.a op.b)
+  ///   Op/Neg: op.a
+  /// }
+  ///
+  /// // Transforms to:
+  /// foo = λx λop match op {
+  ///   Op/Add: (foo__arm0 x op.a op.b)
+  ///   Op/Sub: (foo__arm0 x op.a op.b)
+  ///   Op/Neg: op.a
+  /// }
+  /// foo__arm0 = λx λop.a λop.b (Foo x op.a op.b)
+  /// ```
+  pub fn merge_match_arms(&mut self) -> usize {
+    let mut count = 0;
+    let mut new_defs = vec![];
+    for def in self.defs.values_mut() {
+      let mut fresh = 0;
+      for rule in def.rules.iter_mut() {
+        count += rule.body.merge_match_arms(&def.name, def.builtin, &mut fresh, &mut new_defs);
+      }
+    }
+
+    // The factored bodies can also have matches with equal arms.
+    while let Some(mut def) = new_defs.pop() {
+      let mut fresh = 0;
+      for rule in def.rules.iter_mut() {
+        count += rule.body.merge_match_arms(&def.name, def.builtin, &mut fresh, &mut new_defs);
+      }
+      self.defs.insert(def.name.clone(), def);
+    }
+
+    count
+  }
+}
+
+impl Term {
+  fn merge_match_arms(
+    &mut self,
+    def_name: &Name,
+    builtin: bool,
+    fresh: &mut usize,
+    new_defs: &mut Vec<Definition>,
+  ) -> usize {
+    maybe_grow(|| {
+      let mut count = 0;
+
+      if let Term::Mat { arms, .. } = self {
+        // Closing the bodies over their fields also compares how the fields are used.
+        let closed = arms
+          .iter()
+          .map(|(_, fields, body)| {
+            let body = body.without_unused_uses().clone();
+            fields.iter().rfold(body, |body, field| Term::lam(Pattern::Var(field.clone()), body))
+          })
+          .collect::<Vec<_>>();
+
+        let mut merged = vec![false; arms.len()];
+        for i in 0 .. arms.len() {
+          if merged[i] || !arms[i].2.without_unused_uses().is_worth_merging() {
+            continue;
+          }
+          let equal = (i + 1 .. arms.len())
+            .filter(|&j| !merged[j] && closed[i].alpha_eq(&closed[j]))
+            .collect::<Vec<_>>();
+          if equal.is_empty() {
+            continue;
+          }
+
+          let mut free_vars = closed[i].free_vars().into_keys().collect::<Vec<_>>();
+          free_vars.sort();
+
+          let new_nam = Name::new(format!("{def_name}__arm{fresh}"));
+          *fresh += 1;
+
+          let body = free_vars
+            .iter()
+            .rfold(closed[i].clone(), |body, var| Term::lam(Pattern::Var(Some(var.clone())), body));
+          new_defs.push(Definition {
+            name: new_nam.clone(),
+            rules: vec![Rule { pats: vec![], body, span: None }],
+            builtin,
+          });
+
+          for j in std::iter::once(i).chain(equal) {
+            merged[j] = true;
+            let (_, fields, body) = &mut arms[j];
+            let args = free_vars.iter().map(|nam| Term::Var { nam: nam.clone() });
+            let args = args.chain(fields.iter().cloned().map(Term::var_or_era));
+            *body = Term::call(Term::Ref { nam: new_nam.clone() }, args);
+            count += 1;
+          }
+        }
+      }
+
+      for child in self.children_mut() {
+        count += child.merge_match_arms(def_name, builtin, fresh, new_defs);
+      }
+      count
+    })
+  }
+
+  /// Skips the `use`s at the start of the term whose variable is not used,
+  /// like the one binding the matched constructor that is added to each arm.
+  fn without_unused_uses(&self) -> &Term {
+    match self {
+      Term::Use { nam, nxt, .. } if nam.as_ref().map_or(true, |nam| !nxt.free_vars().contains_key(nam)) => {
+        nxt.without_unused_uses()
+      }
+      _ => self,
+    }
+  }
+
+  /// Whether replacing this arm body by a call can make it smaller.
+  fn is_worth_merging(&self) -> bool {
+    self.children().next().is_some() && !self.has_unscoped()
+  }
+}
@@ -1,5 +1,6 @@
 pub mod apply_args;
 pub mod apply_use;
+pub mod combine_num_ops;
 pub mod definition_merge;
 pub mod definition_pruning;
 pub mod desugar_bend;
@@ -7,15 +8,25 @@ pub mod desugar_do_blocks;
 pub mod desugar_fold;
 pub mod desugar_match_defs;
 pub mod desugar_open;
+pub mod eliminate_identities;
 pub mod encode_adts;
 pub mod encode_match_terms;
 pub mod expand_generated;
 pub mod fix_match_defs;
 pub mod fix_match_terms;
 pub mod float_combinators;
+pub mod float_lets;
+pub mod inline;
 pub mod linearize_matches;
 pub mod linearize_vars;
+pub mod merge_match_arms;
+pub mod monomorphize;
+pub mod propagate_constants;
 pub mod resolve_refs;
+pub mod resugar_builtins;
 pub mod resugar_list;
 pub mod resugar_string;
+pub mod select_definitions;
+pub mod simplify_known_matches;
+pub mod sort_definitions;
 pub mod unique_names;
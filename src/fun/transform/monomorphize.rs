@@ -0,0 +1,213 @@
+use crate::{
+  fun::{Adts, Book, Constructors, Definition, Name, Pattern, Rule, Tag, Term},
+  maybe_grow,
+};
+use indexmap::IndexMap;
+
+/// For each argument of a definition, the constructor passed in every call, if they all agree.
+type CallCtrs = IndexMap<Name, Vec<Option<Name>>>;
+
+impl Book {
+  /// Specializes the definitions that are always called with the same constructor in some argument.
+  ///
+  /// A copy of the definition takes the fields of the constructor as separate arguments
+  /// and rebuilds it with a `let`, so that `simplify_known_matches` can resolve the matches on it.
+  /// Every call is then changed to the copy, passing the fields directly,
+  /// and the generic version, which is no longer referenced, is removed.
+  ///
+  /// Returns how many definitions were specialized.
+  ///
+  /// Without type inference, the constructor is only known where it's written in the call
+  /// and applied to all of its fields. If some call passes anything else in that argument,
+  /// or if the definition is referenced without being applied, the generic version is kept.
+  ///
+  /// Must be called after `make_var_names_unique` and before `simplify_known_matches`.
+  ///
+  /// Example:
+  /// ```bend
+  /// Foo = λx λy match x { Maybe/Some: (+ x.val y); Maybe/None: y }
+  /// main = (+ (Foo (Maybe/Some 1) 2) (Foo (Maybe/Some 3) 4))
+  ///
+  /// // Transforms to:
+  /// Foo__mono = λx.val λy let x = (Maybe/Some x.val); match x { Maybe/Some: (+ x.val y); Maybe/None: y }
+  /// main = (+ (Foo__mono 1 2) (Foo__mono 3 4))
+  /// ```
+  pub fn monomorphize(&mut self) -> usize {
+    let mut calls = CallCtrs::new();
+    for def in self.defs.values() {
+      for rule in &def.rules {
+        rule.body.call_ctrs(&self.ctrs, &self.adts, &self.defs, &mut calls);
+      }
+    }
+
+    let mut specialized = IndexMap::new();
+    for (def_name, ctrs) in calls {
+      if ctrs.iter().all(Option::is_none)
+        || self.ctrs.contains_key(&def_name)
+        || self.entrypoint.as_ref() == Some(&def_name)
+      {
+        continue;
+      }
+      let def = &self.defs[&def_name];
+      let new_name = Name::new(format!("{def_name}__mono"));
+      let body = specialize_body(&def.rules[0].body, &ctrs, &self.ctrs, &self.adts);
+      let rules = vec![Rule { pats: vec![], body, span: None }];
+      let new_def = Definition { name: new_name.clone(), rules, builtin: def.builtin };
+      self.defs.shift_remove(&def_name);
+      self.defs.insert(new_name.clone(), new_def);
+      specialized.insert(def_name, (new_name, ctrs));
+    }
+
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.specialize_calls(&specialized);
+      }
+    }
+    specialized.len()
+  }
+}
+
+impl Term {
+  /// Collects the constructors passed to each argument of the definitions called in this term.
+  fn call_ctrs(
+    &self,
+    ctrs: &Constructors,
+    adts: &Adts,
+    defs: &IndexMap<Name, Definition>,
+    calls: &mut CallCtrs,
+  ) {
+    maybe_grow(|| {
+      let (fun, args) = self.app_spine();
+      if let Term::Ref { nam } = fun
+        && let Some(def) = defs.get(nam)
+      {
+        let params = def.rules[0].body.lam_params().len();
+        let passed = (0 .. params)
+          .map(|i| args.get(i).and_then(|arg| arg.full_ctr(ctrs, adts)).map(|(ctr, _)| ctr.clone()))
+          .collect::<Vec<_>>();
+        let agreed = calls.entry(nam.clone()).or_insert_with(|| passed.clone());
+        for (agreed, passed) in agreed.iter_mut().zip(passed) {
+          if *agreed != passed {
+            *agreed = None;
+          }
+        }
+        for arg in args {
+          arg.call_ctrs(ctrs, adts, defs, calls);
+        }
+        return;
+      }
+
+      for child in self.children() {
+        child.call_ctrs(ctrs, adts, defs, calls);
+      }
+    })
+  }
+
+  /// Changes the calls of the specialized definitions to their specialized copy,
+  /// passing the fields of the known constructors instead of the constructors.
+  fn specialize_calls(&mut self, specialized: &IndexMap<Name, (Name, Vec<Option<Name>>)>) {
+    maybe_grow(|| {
+      let (fun, args) = self.app_spine();
+      if let Term::Ref { nam } = fun
+        && let Some((new_name, ctrs)) = specialized.get(nam)
+      {
+        let mut new_args = vec![];
+        for (i, arg) in args.into_iter().enumerate() {
+          if ctrs.get(i).is_some_and(Option::is_some) {
+            let (_, fields) = arg.app_spine();
+            new_args.extend(fields.into_iter().cloned());
+          } else {
+            new_args.push(arg.clone());
+          }
+        }
+        *self = Term::call(Term::r#ref(new_name), new_args);
+        let (_, args) = self.app_spine_mut();
+        for arg in args {
+          arg.specialize_calls(specialized);
+        }
+        return;
+      }
+
+      for child in self.children_mut() {
+        child.specialize_calls(specialized);
+      }
+    })
+  }
+
+  /// The function at the head of a chain of untagged applications, and its arguments in order.
+  fn app_spine(&self) -> (&Term, Vec<&Term>) {
+    let mut args = vec![];
+    let mut fun = self;
+    while let Term::App { tag: Tag::Static, fun: app_fun, arg } = fun {
+      args.push(arg.as_ref());
+      fun = app_fun;
+    }
+    args.reverse();
+    (fun, args)
+  }
+
+  fn app_spine_mut(&mut self) -> (&mut Term, Vec<&mut Term>) {
+    let mut args = vec![];
+    let mut fun = self;
+    while let Term::App { tag: Tag::Static, fun: app_fun, arg } = fun {
+      args.push(arg.as_mut());
+      fun = app_fun;
+    }
+    args.reverse();
+    (fun, args)
+  }
+
+  /// If this term is a constructor applied to all its fields, returns the constructor and the fields.
+  fn full_ctr(&self, ctrs: &Constructors, adts: &Adts) -> Option<(&Name, Vec<&Term>)> {
+    let (fun, args) = self.app_spine();
+    let Term::Ref { nam } = fun else { return None };
+    let adt = ctrs.get(nam)?;
+    (adts[adt].ctrs[nam].len() == args.len()).then_some((nam, args))
+  }
+
+  /// The variables bound by the untagged lambdas at the start of this term.
+  fn lam_params(&self) -> Vec<&Name> {
+    let mut params = vec![];
+    let mut bod = self;
+    while let Term::Lam { tag: Tag::Static, pat: box Pattern::Var(Some(nam)), bod: lam_bod } = bod {
+      params.push(nam);
+      bod = lam_bod;
+    }
+    params
+  }
+}
+
+/// Builds the body of the specialized copy of a definition, that takes the fields of the known
+/// constructors as arguments and rebuilds the constructors with `let`s before the original body.
+fn specialize_body(body: &Term, ctrs: &[Option<Name>], ctr_adts: &Constructors, adts: &Adts) -> Term {
+  let params = body.lam_params();
+  let mut bod = body;
+  for _ in 0 .. params.len() {
+    if let Term::Lam { bod: lam_bod, .. } = bod {
+      bod = lam_bod;
+    }
+  }
+
+  // Built from the last parameter, since the lambdas are added from the inside out.
+  let mut new_params = vec![];
+  let mut bod = bod.clone();
+  for (param, ctr) in params.iter().zip(ctrs).rev() {
+    let Some(ctr) = ctr else {
+      new_params.push((*param).clone());
+      continue;
+    };
+    let fields = adts[&ctr_adts[ctr]].ctrs[ctr]
+      .iter()
+      .map(|field| Name::new(format!("{param}.{}", field.nam)))
+      .collect::<Vec<_>>();
+    let val = Term::call(Term::r#ref(ctr), fields.iter().map(|nam| Term::Var { nam: nam.clone() }));
+    bod = Term::Let {
+      pat: Box::new(Pattern::Var(Some((*param).clone()))),
+      val: Box::new(val),
+      nxt: Box::new(bod),
+    };
+    new_params.extend(fields.into_iter().rev());
+  }
+
+  new_params.into_iter().fold(bod, |bod, nam| Term::lam(Pattern::Var(Some(nam)), bod))
+}
@@ -0,0 +1,83 @@
+use crate::{
+  fun::{Book, Constructors, Name, Pattern, Term},
+  maybe_grow,
+};
+
+impl Book {
+  /// Substitutes the variables bound by `let`s to constants by their values, removing the `let`s.
+  ///
+  /// Returns how many `let`s were removed.
+  ///
+  /// Numbers, erasers, references and variables are substituted in every use.
+  /// Constructors applied to those are only substituted if the variable is used once,
+  /// so that the constructor isn't built more than once.
+  /// `let`s of constants whose variable is never used are removed.
+  ///
+  /// Must be called after `make_var_names_unique`,
+  /// since moving a value that has variables must not capture them.
+  ///
+  /// Example:
+  /// ```bend
+  /// let x = 2; let y = (Maybe/Some x); (Foo x x y)
+  ///
+  /// // Transforms to:
+  /// (Foo 2 2 (Maybe/Some 2))
+  /// ```
+  pub fn propagate_constants(&mut self) -> usize {
+    let mut count = 0;
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        count += rule.body.propagate_constants(&self.ctrs);
+      }
+    }
+    count
+  }
+}
+
+impl Term {
+  fn propagate_constants(&mut self, ctrs: &Constructors) -> usize {
+    maybe_grow(|| {
+      if let Term::Let { pat: box Pattern::Var(Some(nam)), val, nxt } = self {
+        let is_atom = val.is_constant_atom();
+        if is_atom || val.is_constant_ctr(ctrs) {
+          let uses = nxt.count_uses(nam);
+          if is_atom || uses <= 1 {
+            let val = std::mem::take(val.as_mut());
+            let mut nxt = std::mem::take(nxt.as_mut());
+            nxt.subst(nam, &val);
+            *self = nxt;
+            return 1 + self.propagate_constants(ctrs);
+          }
+        }
+      }
+
+      self.children_mut().map(|child| child.propagate_constants(ctrs)).sum()
+    })
+  }
+
+  /// How many times the variable `nam` appears in this term.
+  /// Since the names are unique, every appearance is a use of the same variable.
+  fn count_uses(&self, nam: &Name) -> usize {
+    maybe_grow(|| match self {
+      Term::Var { nam: var } => (var == nam) as usize,
+      _ => self.children().map(|child| child.count_uses(nam)).sum(),
+    })
+  }
+
+  /// Whether this term can be copied to every use without duplicating any work.
+  fn is_constant_atom(&self) -> bool {
+    matches!(self, Term::Num { .. } | Term::Era | Term::Ref { .. } | Term::Var { .. })
+  }
+
+  /// Whether this term is a constructor applied only to atoms.
+  fn is_constant_ctr(&self, ctrs: &Constructors) -> bool {
+    let mut fun = self;
+    while let Term::App { fun: app_fun, arg, .. } = fun {
+      if !arg.is_constant_atom() {
+        return false;
+      }
+      fun = app_fun;
+    }
+    matches!(fun, Term::Ref { nam } if ctrs.contains_key(nam))
+  }
+}
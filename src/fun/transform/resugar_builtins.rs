@@ -0,0 +1,94 @@
+use crate::{fun::Term, maybe_grow};
+use std::{collections::BTreeSet, fmt, sync::Arc};
+
+/// A builtin type whose lambda-encoded values can be turned back into their syntax sugar on readback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuiltinSugar {
+  /// String literals, see [Term::resugar_strings].
+  String,
+  /// List literals, see [Term::resugar_lists].
+  List,
+}
+
+impl BuiltinSugar {
+  pub const ALL: [BuiltinSugar; 2] = [BuiltinSugar::String, BuiltinSugar::List];
+
+  /// The set with every builtin sugar.
+  pub fn all() -> BTreeSet<BuiltinSugar> {
+    BTreeSet::from(Self::ALL)
+  }
+}
+
+/// A custom rule to show a part of a readback result in a friendlier form, like a user type
+/// that encodes a map shown as `{k: v}`. They're registered in [`crate::RunOpts::resugarers`].
+///
+/// The function returns whether it changed the term.
+#[derive(Clone)]
+pub struct Resugarer(Arc<dyn Fn(&mut Term) -> bool + Send + Sync>);
+
+impl Resugarer {
+  pub fn new(f: impl Fn(&mut Term) -> bool + Send + Sync + 'static) -> Self {
+    Self(Arc::new(f))
+  }
+}
+
+impl fmt::Debug for Resugarer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Resugarer")
+  }
+}
+
+impl Term {
+  /// Applies the custom resugarers to every subterm, from the outside in.
+  ///
+  /// At each subterm the resugarers are tried in order until one of them changes it,
+  /// and then the subterms of the result are visited.
+  pub fn resugar_custom(&mut self, resugarers: &[Resugarer]) {
+    maybe_grow(|| {
+      for resugarer in resugarers {
+        if (resugarer.0)(self) {
+          break;
+        }
+      }
+      for child in self.children_mut() {
+        child.resugar_custom(resugarers);
+      }
+    })
+  }
+
+  /// Resugars only the given builtin types, leaving the others in their constructor form.
+  pub fn resugar_builtins(&mut self, sugars: &BTreeSet<BuiltinSugar>) {
+    if sugars.contains(&BuiltinSugar::String) {
+      self.resugar_strings();
+    }
+    if sugars.contains(&BuiltinSugar::List) {
+      self.resugar_lists();
+    }
+  }
+}
+
+#[test]
+fn resugar_some_builtins() {
+  use crate::fun::{builtins, FanKind, Name, Num, Pattern, Tag};
+
+  // λc λ* (c head Nil)
+  let cons = |head: Term, nil: &str| {
+    let app = Term::call(Term::Var { nam: Name::new("c") }, [head, Term::r#ref(nil)]);
+    Term::lam(Pattern::Var(Some(Name::new("c"))), Term::lam(Pattern::Var(None), app))
+  };
+  let resugar = |sugars: &[BuiltinSugar]| {
+    let mut term = Term::Fan {
+      fan: FanKind::Tup,
+      tag: Tag::Static,
+      els: vec![
+        cons(Term::Num { val: Num::U24(65) }, builtins::SNIL),
+        cons(Term::Num { val: Num::U24(1) }, builtins::LNIL),
+      ],
+    };
+    term.resugar_builtins(&BTreeSet::from_iter(sugars.iter().copied()));
+    term.display_oneline().to_string()
+  };
+  assert_eq!(resugar(&BuiltinSugar::ALL), r#"("A", [1])"#);
+  assert_eq!(resugar(&[BuiltinSugar::List]), "(λc λ* (c 65 String/Nil), [1])");
+  assert_eq!(resugar(&[]), "(λc λ* (c 65 String/Nil), λc λ* (c 1 List/Nil))");
+}
@@ -0,0 +1,72 @@
+use crate::{
+  diagnostics::Diagnostics,
+  fun::{transform::definition_merge::MERGE_SEPARATOR, Ctx, Name, Term},
+};
+use indexmap::IndexSet;
+
+impl Ctx<'_> {
+  /// Keeps only the definitions selected by `keep` and the ones they need,
+  /// so that a slice of a big book can be compiled by itself.
+  ///
+  /// Starting from the entrypoint and the selected definitions, follows their references,
+  /// always keeping builtins and constructors and the definitions generated from selected ones.
+  /// Everything that is not reached is removed.
+  /// A reference to a definition that is not selected is an error, reported on the definition using it.
+  ///
+  /// Merged definitions are selected if any of the definitions they were merged from is.
+  pub fn select_definitions(&mut self, keep: impl Fn(&Name) -> bool) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let is_selected = |def_name: &Name| {
+      let def = &self.book.defs[def_name];
+      def.builtin
+        || self.book.ctrs.contains_key(def_name)
+        || def_name.split(MERGE_SEPARATOR).any(|nam| keep(&Name::new(nam).def_name_from_generated()))
+    };
+
+    let mut selected = IndexSet::new();
+    selected.extend(self.book.entrypoint.clone());
+    for def_name in self.book.defs.keys() {
+      if !self.book.defs[def_name].builtin && is_selected(def_name) {
+        selected.insert(def_name.clone());
+      }
+    }
+
+    let mut i = 0;
+    while let Some(def_name) = selected.get_index(i).cloned() {
+      i += 1;
+      for rule in &self.book.defs[&def_name].rules {
+        for nam in rule.body.refs() {
+          if !self.book.defs.contains_key(nam) || selected.contains(nam) {
+            continue;
+          }
+          if is_selected(nam) {
+            selected.insert(nam.clone());
+          } else {
+            let err = format!("Reference to definition '{nam}', which was not selected for compilation.");
+            self.info.add_rule_error(err, def_name.clone());
+          }
+        }
+      }
+    }
+
+    self.book.defs.retain(|def_name, _| selected.contains(def_name));
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  /// The names of the definitions referenced in this term.
+  pub fn refs(&self) -> IndexSet<&Name> {
+    let mut refs = IndexSet::new();
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      if let Term::Ref { nam } = term {
+        refs.insert(nam);
+      }
+      to_visit.extend(term.children());
+    }
+    refs
+  }
+}
@@ -0,0 +1,127 @@
+use crate::{
+  fun::{Adts, Book, Constructors, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+impl Book {
+  /// Resolves at compile time the matches on variables bound to a known constructor,
+  /// replacing them by the arm of that constructor.
+  ///
+  /// Returns how many matches were simplified.
+  ///
+  /// A variable is known to hold a constructor when it's bound by a `let` or a `use`
+  /// to that constructor applied to all of its fields, as long as every argument is
+  /// a variable, a number, an eraser or a reference, so that substituting the fields
+  /// in the arm doesn't duplicate any work.
+  /// Inside the arm of a match on a variable, that variable is also known to be the arm's constructor,
+  /// so a later match on it is replaced by its arm with the fields of the outer arm substituted in.
+  ///
+  /// Since only the arm that would be selected at runtime is kept,
+  /// exhaustiveness and the order of evaluation of the arms are preserved.
+  ///
+  /// Must be called after `fix_match_terms` and `make_var_names_unique`,
+  /// since the substitutions rely on the variable names being unique.
+  ///
+  /// Example:
+  /// ```bend
+  /// let x = (Maybe/Some y); match x { Maybe/Some: (Foo x.val); Maybe/None: 0 }
+  ///
+  /// // Transforms to:
+  /// let x = (Maybe/Some y); (Foo y)
+  ///
+  /// match x {
+  ///   Maybe/Some: match x { Maybe/Some: x.val; Maybe/None: 0 }
+  ///   Maybe/None: 1
+  /// }
+  ///
+  /// // Transforms to:
+  /// match x {
+  ///   Maybe/Some: x.val
+  ///   Maybe/None: 1
+  /// }
+  /// ```
+  pub fn simplify_known_matches(&mut self) -> usize {
+    let mut count = 0;
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        count += rule.body.simplify_known_matches(&self.ctrs, &self.adts, &mut HashMap::new());
+      }
+    }
+    count
+  }
+}
+
+type KnownCtrs = HashMap<Name, (Name, Vec<Term>)>;
+
+impl Term {
+  fn simplify_known_matches(&mut self, ctrs: &Constructors, adts: &Adts, known: &mut KnownCtrs) -> usize {
+    maybe_grow(|| {
+      let bound = match self {
+        Term::Let { pat: box Pattern::Var(Some(nam)), val, .. } => Some((nam, val)),
+        // `fix_match_terms` rebinds the scrutinee in each arm with a `use` of the matched constructor.
+        Term::Use { nam: Some(nam), val, .. } => Some((nam, val)),
+        _ => None,
+      };
+      if let Some((nam, val)) = bound
+        && let Some(ctr) = val.known_ctr(ctrs, adts)
+      {
+        known.insert(nam.clone(), ctr);
+      }
+
+      let Term::Mat { arg: box Term::Var { nam }, arms, .. } = self else {
+        return self.children_mut().map(|child| child.simplify_known_matches(ctrs, adts, known)).sum();
+      };
+
+      if let Some((ctr, args)) = known.get(nam)
+        && let Some(arm) = arms.iter_mut().find(|(arm_ctr, ..)| arm_ctr.as_ref() == Some(ctr))
+      {
+        let (_, fields, body) = arm;
+        let mut body = std::mem::take(body);
+        for (field, arg) in fields.iter().zip(args) {
+          if let Some(field) = field {
+            body.subst(field, arg);
+          }
+        }
+        *self = body;
+        return 1 + self.simplify_known_matches(ctrs, adts, known);
+      }
+
+      // Each arm knows which constructor the scrutinee is, as long as all its fields are bound.
+      let nam = nam.clone();
+      let mut count = 0;
+      for (ctr, fields, body) in arms.iter_mut() {
+        let fields = fields.iter().map(|field| Some(Term::Var { nam: field.clone()? })).collect();
+        if let (Some(ctr), Some(fields)) = (ctr, fields) {
+          known.insert(nam.clone(), (ctr.clone(), fields));
+          count += body.simplify_known_matches(ctrs, adts, known);
+          known.remove(&nam);
+        } else {
+          count += body.simplify_known_matches(ctrs, adts, known);
+        }
+      }
+      count
+    })
+  }
+
+  /// If this term is a constructor applied to all its fields, with only atoms as arguments,
+  /// returns the constructor name and its arguments.
+  fn known_ctr(&self, ctrs: &Constructors, adts: &Adts) -> Option<(Name, Vec<Term>)> {
+    let mut args = vec![];
+    let mut fun = self;
+    while let Term::App { fun: app_fun, arg, .. } = fun {
+      if !matches!(arg.as_ref(), Term::Var { .. } | Term::Num { .. } | Term::Era | Term::Ref { .. }) {
+        return None;
+      }
+      args.push(arg.as_ref().clone());
+      fun = app_fun;
+    }
+    let Term::Ref { nam } = fun else { return None };
+    let adt = ctrs.get(nam)?;
+    if adts[adt].ctrs[nam].len() != args.len() {
+      return None;
+    }
+    args.reverse();
+    Some((nam.clone(), args))
+  }
+}
@@ -0,0 +1,52 @@
+use crate::fun::{Book, Name, Term};
+use indexmap::IndexSet;
+use std::collections::VecDeque;
+
+impl Book {
+  /// Orders the definitions deterministically, independent of the order they were written or generated in.
+  ///
+  /// The entrypoint comes first, followed by the definitions reachable from it in breadth-first order,
+  /// so that a definition always comes after the first one that references it.
+  /// References of the same definition are visited in alphabetical order.
+  /// The definitions not reachable from the entrypoint come last, in alphabetical order.
+  pub fn sort_definitions(&mut self) {
+    let mut order = IndexSet::new();
+
+    if let Some(main) = &self.entrypoint
+      && self.defs.contains_key(main)
+    {
+      order.insert(main.clone());
+      let mut to_visit = VecDeque::from([main.clone()]);
+      while let Some(def_name) = to_visit.pop_front() {
+        let mut refs = vec![];
+        for rule in &self.defs[&def_name].rules {
+          rule.body.collect_refs(&mut refs);
+        }
+        refs.sort();
+        for nam in refs {
+          if self.defs.contains_key(&nam) && order.insert(nam.clone()) {
+            to_visit.push_back(nam);
+          }
+        }
+      }
+    }
+
+    let mut rest = self.defs.keys().filter(|name| !order.contains(*name)).cloned().collect::<Vec<_>>();
+    rest.sort();
+    order.extend(rest);
+
+    self.defs.sort_by_cached_key(|name, _| order.get_index_of(name));
+  }
+}
+
+impl Term {
+  fn collect_refs(&self, refs: &mut Vec<Name>) {
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      if let Term::Ref { nam } = term {
+        refs.push(nam.clone());
+      }
+      to_visit.extend(term.children());
+    }
+  }
+}
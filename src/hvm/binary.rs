@@ -0,0 +1,238 @@
+//! A compact binary encoding of compiled books, to load them without parsing their text form.
+//!
+//! The encoding starts with [MAGIC] and a format version, followed by a table with every name
+//! used in the book. Net names, references and variables are then written as indices into that table.
+//! Integers are written as LEB128 varints.
+
+use crate::{fun::term_to_net::Labels, maybe_grow};
+use hvmc::ast::{Book, Net, Tree};
+use indexmap::IndexSet;
+
+pub const MAGIC: &[u8; 4] = b"BEND";
+const VERSION: u8 = 1;
+
+const TAG_ERA: u8 = 0;
+const TAG_NUM: u8 = 1;
+const TAG_REF: u8 = 2;
+const TAG_CTR: u8 = 3;
+const TAG_OP: u8 = 4;
+const TAG_MAT: u8 = 5;
+const TAG_VAR: u8 = 6;
+
+/// Encodes a compiled book and its labels.
+pub fn book_to_bytes(book: &Book, labels: &Labels) -> Vec<u8> {
+  let mut names = IndexSet::new();
+  for (name, net) in &book.nets {
+    names.insert(name.as_str());
+    for tree in net.trees() {
+      collect_names(tree, &mut names);
+    }
+  }
+
+  let mut out = Vec::from(*MAGIC);
+  out.push(VERSION);
+
+  write_str(&mut out, &labels.to_string());
+
+  write_uint(&mut out, names.len() as u64);
+  for name in &names {
+    write_str(&mut out, name);
+  }
+
+  write_uint(&mut out, book.nets.len() as u64);
+  for (name, net) in &book.nets {
+    write_uint(&mut out, names.get_index_of(name.as_str()).unwrap() as u64);
+    write_tree(&mut out, &net.root, &names);
+    write_uint(&mut out, net.redexes.len() as u64);
+    for (safe, a, b) in &net.redexes {
+      out.push(*safe as u8);
+      write_tree(&mut out, a, &names);
+      write_tree(&mut out, b, &names);
+    }
+  }
+  out
+}
+
+/// Decodes a book encoded by [book_to_bytes].
+pub fn bytes_to_book(bytes: &[u8]) -> Result<(Book, Labels), String> {
+  let mut reader = Reader { bytes, pos: 0 };
+
+  if reader.take(MAGIC.len())? != MAGIC {
+    return Err("Not a compiled Bend book.".to_string());
+  }
+  let version = reader.byte()?;
+  if version != VERSION {
+    return Err(format!("Unsupported compiled book version {version}, expected {VERSION}."));
+  }
+
+  let labels = reader.str()?.parse()?;
+
+  let names = (0 .. reader.uint()?).map(|_| reader.str()).collect::<Result<Vec<_>, _>>()?;
+
+  let mut book = Book::default();
+  for _ in 0 .. reader.uint()? {
+    let name = reader.name(&names)?;
+    let root = reader.tree(&names)?;
+    let redexes = (0 .. reader.uint()?)
+      .map(|_| Ok((reader.byte()? != 0, reader.tree(&names)?, reader.tree(&names)?)))
+      .collect::<Result<_, String>>()?;
+    book.nets.insert(name, Net { root, redexes });
+  }
+
+  if reader.pos != bytes.len() {
+    return Err("Unexpected data after the end of the compiled book.".to_string());
+  }
+  Ok((book, labels))
+}
+
+fn collect_names<'a>(tree: &'a Tree, names: &mut IndexSet<&'a str>) {
+  maybe_grow(|| match tree {
+    Tree::Ref { nam } | Tree::Var { nam } => {
+      names.insert(nam.as_str());
+    }
+    _ => {
+      for child in tree.children() {
+        collect_names(child, names);
+      }
+    }
+  })
+}
+
+fn write_tree(out: &mut Vec<u8>, tree: &Tree, names: &IndexSet<&str>) {
+  maybe_grow(|| match tree {
+    Tree::Era => out.push(TAG_ERA),
+    Tree::Num { val } => {
+      out.push(TAG_NUM);
+      write_uint(out, *val as u64);
+    }
+    Tree::Ref { nam } => {
+      out.push(TAG_REF);
+      write_uint(out, names.get_index_of(nam.as_str()).unwrap() as u64);
+    }
+    Tree::Ctr { lab, ports } => {
+      out.push(TAG_CTR);
+      write_uint(out, *lab as u64);
+      write_uint(out, ports.len() as u64);
+      for port in ports {
+        write_tree(out, port, names);
+      }
+    }
+    Tree::Op { fst, snd } => {
+      out.push(TAG_OP);
+      write_tree(out, fst, names);
+      write_tree(out, snd, names);
+    }
+    Tree::Mat { zero, succ, out: ret } => {
+      out.push(TAG_MAT);
+      write_tree(out, zero, names);
+      write_tree(out, succ, names);
+      write_tree(out, ret, names);
+    }
+    Tree::Var { nam } => {
+      out.push(TAG_VAR);
+      write_uint(out, names.get_index_of(nam.as_str()).unwrap() as u64);
+    }
+  })
+}
+
+fn write_uint(out: &mut Vec<u8>, mut val: u64) {
+  while val >= 0x80 {
+    out.push((val as u8 & 0x7f) | 0x80);
+    val >>= 7;
+  }
+  out.push(val as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+  write_uint(out, s.len() as u64);
+  out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+    let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len());
+    let bytes = &self.bytes[self.pos .. end.ok_or("Unexpected end of the compiled book.")?];
+    self.pos += len;
+    Ok(bytes)
+  }
+
+  fn byte(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn uint(&mut self) -> Result<u64, String> {
+    let mut val = 0u64;
+    for shift in (0 .. 64).step_by(7) {
+      let byte = self.byte()?;
+      val |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(val);
+      }
+    }
+    Err("Invalid integer in the compiled book.".to_string())
+  }
+
+  fn str(&mut self) -> Result<String, String> {
+    let len = self.uint()? as usize;
+    let bytes = self.take(len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| "Invalid name in the compiled book.".to_string())
+  }
+
+  fn name(&mut self, names: &[String]) -> Result<String, String> {
+    let idx = self.uint()? as usize;
+    names.get(idx).cloned().ok_or_else(|| format!("Invalid name index {idx} in the compiled book."))
+  }
+
+  fn tree(&mut self, names: &[String]) -> Result<Tree, String> {
+    maybe_grow(|| {
+      let tree = match self.byte()? {
+        TAG_ERA => Tree::Era,
+        TAG_NUM => {
+          let val = self.uint()?;
+          let val = u32::try_from(val).map_err(|_| format!("Invalid number {val} in the compiled book."))?;
+          Tree::Num { val }
+        }
+        TAG_REF => Tree::Ref { nam: self.name(names)? },
+        TAG_CTR => {
+          let lab = self.uint()?;
+          let lab = u16::try_from(lab).map_err(|_| format!("Invalid label {lab} in the compiled book."))?;
+          let ports = (0 .. self.uint()?).map(|_| self.tree(names)).collect::<Result<_, _>>()?;
+          Tree::Ctr { lab, ports }
+        }
+        TAG_OP => Tree::Op { fst: Box::new(self.tree(names)?), snd: Box::new(self.tree(names)?) },
+        TAG_MAT => Tree::Mat {
+          zero: Box::new(self.tree(names)?),
+          succ: Box::new(self.tree(names)?),
+          out: Box::new(self.tree(names)?),
+        },
+        TAG_VAR => Tree::Var { nam: self.name(names)? },
+        tag => return Err(format!("Invalid node tag {tag} in the compiled book.")),
+      };
+      Ok(tree)
+    })
+  }
+}
+
+#[test]
+fn book_bytes_round_trip() {
+  let code = "
+    @main = a & @Foo ~ (1 (2 a))
+    @Foo = ($(:[+] $(a b)) (a b))
+    @max = ({$(:[>] $(a ?(((b (* b)) (* (* (c c)))) (d (e f))))) e} ({a d} f))
+    @Bar = a & $(1 ?(((b (* b)) (* (* (c c)))) a)) ~ [+0]
+  ";
+  let book: Book = code.parse().unwrap();
+  let bytes = book_to_bytes(&book, &Labels::default());
+  assert!(bytes.len() < book.to_string().len());
+
+  let (decoded, _) = bytes_to_book(&bytes).unwrap();
+  assert_eq!(decoded, book);
+
+  assert!(bytes_to_book(&bytes[.. bytes.len() - 1]).is_err());
+  assert!(bytes_to_book(b"not a book").is_err());
+}
@@ -0,0 +1,88 @@
+use crate::maybe_grow;
+use hvmc::ast::{Net, Tree};
+use std::collections::HashMap;
+
+/// The label and the variables of the last two ports of a constructor, `{lab x y}`.
+type CtrVars<'a> = (u16, &'a str, &'a str);
+
+/// Replaces each pair of constructors with the same label that are linked aux to aux,
+/// `{lab x y} ... {lab x y}`, by a wire, `x ... x`.
+///
+/// Unlike [`Net::eta_reduce`] from hvm-core, nodes whose ports are both erasers or both the same number,
+/// like `(* *)` or `(1 1)`, are kept.
+/// They interact like a single eraser or number, since those are copied by constructors,
+/// but they're read back as a different term, so removing them changes the result of the program.
+pub fn eta_reduce(net: &mut Net) {
+  while eta_reduce_step(net) {}
+}
+
+/// Reduces all the pairs of constructors found in the net, returning whether any was found.
+///
+/// Reducing a pair can form a new one with the constructors around it, so this must be repeated.
+fn eta_reduce_step(net: &mut Net) -> bool {
+  let mut counts = HashMap::<CtrVars, usize>::new();
+  for tree in net.trees() {
+    count_ctr_vars(tree, &mut counts);
+  }
+  let reducible = counts
+    .into_iter()
+    .filter(|((_, x, y), count)| *count == 2 && x != y)
+    .map(|((lab, x, y), _)| (lab, x.to_string(), y.to_string()))
+    .collect::<Vec<_>>();
+  if reducible.is_empty() {
+    return false;
+  }
+  for tree in net.trees_mut() {
+    reduce_ctr_vars(tree, &reducible);
+  }
+  true
+}
+
+fn ctr_vars(tree: &Tree) -> Option<CtrVars> {
+  let Tree::Ctr { lab, ports } = tree else { return None };
+  let [.., Tree::Var { nam: x }, Tree::Var { nam: y }] = ports.as_slice() else { return None };
+  Some((*lab, x, y))
+}
+
+fn count_ctr_vars<'a>(tree: &'a Tree, counts: &mut HashMap<CtrVars<'a>, usize>) {
+  maybe_grow(|| {
+    if let Some(key) = ctr_vars(tree) {
+      *counts.entry(key).or_default() += 1;
+    }
+    for child in tree.children() {
+      count_ctr_vars(child, counts);
+    }
+  })
+}
+
+fn reduce_ctr_vars(tree: &mut Tree, reducible: &[(u16, String, String)]) {
+  maybe_grow(|| {
+    for child in tree.children_mut() {
+      reduce_ctr_vars(child, reducible);
+    }
+    if let Some((lab, x, y)) = ctr_vars(tree)
+      && reducible.iter().any(|(r_lab, r_x, r_y)| *r_lab == lab && r_x == x && r_y == y)
+      && let Tree::Ctr { ports, .. } = tree
+    {
+      // `{lab .. x y}` becomes `{lab .. x}`, and the constructor is removed if only `x` is left.
+      ports.pop();
+      if ports.len() == 1 {
+        *tree = ports.pop().unwrap();
+      }
+    }
+  })
+}
+
+#[test]
+fn eta_reduce_keeps_constants() {
+  let reduce = |code: &str| {
+    let mut net = code.parse::<Net>().unwrap();
+    eta_reduce(&mut net);
+    net.to_string()
+  };
+  assert_eq!(reduce("(a (b c)) & (a (b c)) ~ d"), "a\n  & a ~ d");
+  assert_eq!(reduce("({x y} {x y})"), "(x x)");
+  assert_eq!(reduce("(x (y x))"), "(x (y x))");
+  assert_eq!(reduce("(1 1)"), "(1 1)");
+  assert_eq!(reduce("(* *)"), "(* *)");
+}
@@ -1,3 +1,5 @@
 pub mod add_recursive_priority;
+pub mod binary;
 pub mod check_net_size;
+pub mod eta_reduce;
 pub mod mutual_recursion;
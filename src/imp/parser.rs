@@ -1,11 +1,12 @@
 use crate::{
   fun::{
-    parser::{is_num_char, Indent, ParseResult, ParserCommons},
+    parser::{is_num_char, Indent, ParseErrorKind, ParseResult, ParserCommons},
     Adt, Book, CtrField, Name, Num, Op, STRINGS,
   },
   imp::{AssignPattern, Definition, Enum, Expr, InPlaceOp, MatchArm, Stmt, Variant},
   maybe_grow,
 };
+use std::ops::Range;
 use TSPL::Parser;
 
 const PREC: &[&[Op]] = &[
@@ -23,15 +24,20 @@ const PREC: &[&[Op]] = &[
 pub struct PyParser<'i> {
   pub input: &'i str,
   pub index: usize,
+  pub error_ctx: Option<(Range<usize>, ParseErrorKind)>,
 }
 
 impl<'a> PyParser<'a> {
   pub fn new(input: &'a str) -> Self {
-    Self { input, index: 0 }
+    Self { input, index: 0, error_ctx: None }
   }
 }
 
-impl<'a> ParserCommons<'a> for PyParser<'a> {}
+impl<'a> ParserCommons<'a> for PyParser<'a> {
+  fn error_ctx(&mut self) -> &mut Option<(Range<usize>, ParseErrorKind)> {
+    &mut self.error_ctx
+  }
+}
 
 impl<'a> Parser<'a> for PyParser<'a> {
   fn input(&mut self) -> &'a str {
@@ -132,7 +138,7 @@ impl<'a> PyParser<'a> {
       '`' => Expr::Num { val: Num::U24(self.parse_quoted_symbol()?) },
       // String
       '\"' => {
-        let str = self.parse_quoted_string()?;
+        let str = self.parse_string_lit()?;
         let val = STRINGS.get(str);
         Expr::Str { val }
       }
@@ -334,7 +340,7 @@ impl<'a> PyParser<'a> {
       } else {
         let msg = "Unexpected '=' in unnamed argument.".to_string();
         let idx = *self.index();
-        self.with_ctx(Err(msg), idx, idx + 1)
+        self.with_ctx_kind(Err(msg), ParseErrorKind::UnexpectedToken, idx, idx + 1)
       }
     } else {
       Ok((None, arg))
@@ -904,7 +910,7 @@ impl<'a> PyParser<'a> {
     if indent != Indent::Val(0) {
       let msg = "Indentation error. Functions defined with 'def' must be at the start of the line.";
       let idx = *self.index();
-      return self.with_ctx(Err(msg), idx, idx + 1);
+      return self.with_ctx_kind(Err(msg), ParseErrorKind::Indentation, idx, idx + 1);
     }
 
     self.skip_trivia_inline();
@@ -932,7 +938,7 @@ impl<'a> PyParser<'a> {
     if indent != Indent::Val(0) {
       let msg = "Indentation error. Types defined with 'type' must be at the start of the line.";
       let idx = *self.index();
-      return self.with_ctx(Err(msg), idx, idx + 1);
+      return self.with_ctx_kind(Err(msg), ParseErrorKind::Indentation, idx, idx + 1);
     }
 
     self.skip_trivia_inline();
@@ -969,7 +975,7 @@ impl<'a> PyParser<'a> {
     if indent != Indent::Val(0) {
       let msg = "Indentation error. Types defined with 'object' must be at the start of the line.";
       let idx = *self.index();
-      return self.with_ctx(Err(msg), idx, idx + 1);
+      return self.with_ctx_kind(Err(msg), ParseErrorKind::Indentation, idx, idx + 1);
     }
 
     self.skip_trivia_inline();
@@ -1011,7 +1017,8 @@ impl<'a> PyParser<'a> {
     }
     def.order_kwargs(book)?;
     def.gen_map_get();
-    let def = def.to_fun()?;
+    let mut def = def.to_fun()?;
+    def.rules[0].span = Some(self.span(ini_idx, end_idx));
     book.defs.insert(def.name.clone(), def);
     Ok(())
   }
@@ -1034,8 +1041,11 @@ impl<'a> PyParser<'a> {
         let msg = format!("Redefinition of function '{}'.", variant.name);
         return self.with_ctx(Err(msg), ini_idx, end_idx);
       }
-      if book.ctrs.contains_key(&variant.name) {
-        let msg = format!("Redefinition of constructor '{}'.", variant.name);
+      if let Some(other_adt) = book.ctrs.get(&variant.name) {
+        let msg = format!(
+          "Redefinition of constructor '{}', already declared by type '{}'.",
+          variant.name, other_adt
+        );
         return self.with_ctx(Err(msg), ini_idx, end_idx);
       }
       book.ctrs.insert(variant.name.clone(), r#enum.name.clone());
@@ -1062,8 +1072,9 @@ impl<'a> PyParser<'a> {
       let msg = format!("Redefinition of function '{}'.", obj.name);
       return self.with_ctx(Err(msg), ini_idx, end_idx);
     }
-    if book.ctrs.contains_key(&obj.name) {
-      let msg = format!("Redefinition of constructor '{}'.", obj.name);
+    if let Some(other_adt) = book.ctrs.get(&obj.name) {
+      let msg =
+        format!("Redefinition of constructor '{}', already declared by type '{}'.", obj.name, other_adt);
       return self.with_ctx(Err(msg), ini_idx, end_idx);
     }
     book.ctrs.insert(obj.name.clone(), obj.name.clone());
@@ -1078,18 +1089,18 @@ impl<'a> PyParser<'a> {
       (Indent::Eof, Indent::Val(got)) => {
         let msg = format!("Indentation error. Expected end-of-input, got {} spaces.", got);
         let idx = *self.index();
-        self.with_ctx(Err(msg), idx, idx + 1)
+        self.with_ctx_kind(Err(msg), ParseErrorKind::Indentation, idx, idx + 1)
       }
       (Indent::Val(expected), Indent::Eof) => {
         let msg = format!("Indentation error. Expected {} spaces, got end-of-input.", expected);
         let idx = *self.index();
-        self.with_ctx(Err(msg), idx, idx + 1)
+        self.with_ctx_kind(Err(msg), ParseErrorKind::Indentation, idx, idx + 1)
       }
       (Indent::Val(expected), Indent::Val(got)) => {
         if got != expected {
           let msg = format!("Indentation error. Expected {} spaces, got {}.", expected, got);
           let idx = *self.index();
-          self.with_ctx(Err(msg), idx, idx + 1)
+          self.with_ctx_kind(Err(msg), ParseErrorKind::Indentation, idx, idx + 1)
         } else {
           unreachable!()
         }
@@ -11,8 +11,11 @@ impl Definition {
       }
     };
 
-    let rule =
-      fun::Rule { pats: self.params.into_iter().map(|param| fun::Pattern::Var(Some(param))).collect(), body };
+    let rule = fun::Rule {
+      pats: self.params.into_iter().map(|param| fun::Pattern::Var(Some(param))).collect(),
+      body,
+      span: None,
+    };
 
     let def = fun::Definition { name: self.name, rules: vec![rule], builtin: false };
     Ok(def)
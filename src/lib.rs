@@ -1,16 +1,31 @@
 #![feature(box_patterns)]
 #![feature(let_chains)]
 
-use crate::fun::{book_to_nets, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Term};
+use crate::fun::{
+  book_to_nets_with_progress,
+  net_to_term::net_to_term,
+  parser::TermParser,
+  term_to_net::Labels,
+  transform::resugar_builtins::{BuiltinSugar, Resugarer},
+  Book, Ctx, Definition, Name, Rule, Term,
+};
 use diagnostics::{Diagnostics, DiagnosticsConfig, ERR_INDENT_SIZE};
 use hvm::{
   add_recursive_priority::add_recursive_priority,
-  check_net_size::{check_net_sizes, MAX_NET_SIZE},
+  check_net_size::{check_net_sizes, count_nodes, MAX_NET_SIZE},
+  eta_reduce::eta_reduce,
   mutual_recursion,
 };
 use hvmc::ast::Net;
 use net::hvmc_to_net::hvmc_to_net;
-use std::{process::Output, str::FromStr};
+use std::{
+  collections::{BTreeSet, HashMap, HashSet},
+  fmt::Write,
+  hash::{DefaultHasher, Hash, Hasher},
+  path::Path,
+  process::Output,
+  str::FromStr,
+};
 
 pub mod diagnostics;
 pub mod fun;
@@ -22,6 +37,8 @@ pub use fun::load_book::load_file_to_book;
 
 pub const ENTRY_POINT: &str = "main";
 pub const HVM1_ENTRY_POINT: &str = "Main";
+/// The name of the definition holding the expression compiled by [compile_expr].
+const EVAL_ENTRY_POINT: &str = "main__eval";
 
 pub fn check_book(
   book: &mut Book,
@@ -29,30 +46,130 @@ pub fn check_book(
   compile_opts: CompileOpts,
 ) -> Result<Diagnostics, Diagnostics> {
   // TODO: Do the checks without having to do full compilation
-  let res = compile_book(book, compile_opts, diagnostics_cfg, None)?;
+  let res = compile_book(book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
   Ok(res.diagnostics)
 }
 
+/// Like [check_book], but only runs [desugar_book], with all of its optimizations disabled,
+/// without generating the nets.
+///
+/// The checks that `compile_opts` disables with [CompileOpts::disabled_passes] are skipped.
+/// Reports mismatched arities, unbound references, variables and patterns, non-exhaustive matches
+/// and the other checks of the desugaring, but not the errors found only at the net level,
+/// like unbounded recursion or too large nets.
+pub fn validate_book(
+  book: &mut Book,
+  diagnostics_cfg: DiagnosticsConfig,
+  compile_opts: CompileOpts,
+) -> Result<Diagnostics, Diagnostics> {
+  desugar_book(book, compile_opts.set_no_all(), diagnostics_cfg, None)
+}
+
 pub fn compile_book(
   book: &mut Book,
   opts: CompileOpts,
   diagnostics_cfg: DiagnosticsConfig,
   args: Option<Vec<Term>>,
+  hooks: CompileHooks,
 ) -> Result<CompileResult, Diagnostics> {
   let mut diagnostics = desugar_book(book, opts.clone(), diagnostics_cfg, args)?;
+  if let Some(keep) = &hooks.keep {
+    let mut ctx = Ctx { book, info: diagnostics };
+    ctx.select_definitions(keep)?;
+    diagnostics = ctx.info;
+  }
+  compile_desugared_book(book, opts, diagnostics, hooks)
+}
+
+/// Like [compile_book], but stores the compiled hvm-core book in `cache_dir`,
+/// reusing it if the same book was already compiled with the same options.
+///
+/// The lambda-level passes still run on a cache hit, since both their
+/// diagnostics and the desugared book are needed to read back the results.
+/// Warnings from the net-level passes are only reported when the entry is created.
+///
+/// A miss, or an entry that can't be read, just compiles the book normally.
+pub fn compile_book_cached(
+  book: &mut Book,
+  opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  args: Option<Vec<Term>>,
+  cache_dir: &Path,
+) -> Result<CompileResult, Diagnostics> {
+  let key = compile_cache_key(book, &opts, &diagnostics_cfg, &args);
+  let core_book_path = cache_dir.join(format!("{key:016x}.hvm"));
+  let labels_path = cache_dir.join(format!("{key:016x}.labels"));
 
-  let (mut hvm_book, labels) = book_to_nets(book, &mut diagnostics)?;
+  let diagnostics = desugar_book(book, opts.clone(), diagnostics_cfg, args)?;
 
-  if opts.eta {
-    hvm_book.values_mut().for_each(Net::eta_reduce);
+  let cached = || -> Option<_> {
+    let core_book = std::fs::read_to_string(&core_book_path).ok()?.parse().ok()?;
+    let labels = std::fs::read_to_string(&labels_path).ok()?.parse().ok()?;
+    Some((core_book, labels))
+  };
+  if let Some((core_book, labels)) = cached() {
+    return Ok(CompileResult { diagnostics, core_book, labels, nets: None });
   }
 
-  mutual_recursion::check_cycles(&hvm_book, &mut diagnostics)?;
-  if opts.eta {
-    hvm_book.values_mut().for_each(Net::eta_reduce);
+  let res = compile_desugared_book(book, opts, diagnostics, CompileHooks::default())?;
+
+  // Not being able to write to the cache shouldn't stop the compilation.
+  let _ = std::fs::create_dir_all(cache_dir)
+    .and_then(|_| std::fs::write(&labels_path, res.labels.to_string()))
+    .and_then(|_| std::fs::write(&core_book_path, res.core_book.to_string()));
+
+  Ok(res)
+}
+
+/// Hash of everything that affects the result of compiling a book.
+/// Only stable between runs of the same compiler build.
+fn compile_cache_key(
+  book: &Book,
+  opts: &CompileOpts,
+  diagnostics_cfg: &DiagnosticsConfig,
+  args: &Option<Vec<Term>>,
+) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  env!("CARGO_PKG_VERSION").hash(&mut hasher);
+  book.content_hash().hash(&mut hasher);
+  format!("{opts:?}").hash(&mut hasher);
+  format!("{diagnostics_cfg:?}").hash(&mut hasher);
+  args.is_some().hash(&mut hasher);
+  for arg in args.iter().flatten() {
+    arg.to_string().hash(&mut hasher);
   }
+  hasher.finish()
+}
 
-  if opts.inline {
+fn compile_desugared_book(
+  book: &Book,
+  opts: CompileOpts,
+  mut diagnostics: Diagnostics,
+  mut hooks: CompileHooks,
+) -> Result<CompileResult, Diagnostics> {
+  let progress = |idx, total| {
+    if let Some(progress) = &mut hooks.progress {
+      progress(idx, total)
+    }
+  };
+  let (mut hvm_book, labels) = book_to_nets_with_progress(book, &mut diagnostics, progress)?;
+  let nets = hooks.keep_nets.then(|| hvm_book.clone());
+  if let Some(net_pass) = hooks.net_pass {
+    net_pass(&mut hvm_book);
+  }
+
+  if opts.runs(PassKind::Eta) {
+    hvm_book.values_mut().for_each(eta_reduce);
+  }
+
+  if opts.runs(PassKind::CheckCycles) {
+    mutual_recursion::check_cycles(&hvm_book, &mut diagnostics)?;
+  }
+  if opts.runs(PassKind::Eta) {
+    hvm_book.values_mut().for_each(eta_reduce);
+  }
+
+  if opts.runs(PassKind::Inline) {
     diagnostics.start_pass();
     if let Err(e) = hvm_book.inline() {
       diagnostics.add_book_error(format!("During inlining:\n{:ERR_INDENT_SIZE$}{}", "", e));
@@ -69,9 +186,11 @@ pub fn compile_book(
     check_net_sizes(&hvm_book, &mut diagnostics)?;
   }
 
-  add_recursive_priority(&mut hvm_book);
+  if opts.runs(PassKind::RecursivePriority) {
+    add_recursive_priority(&mut hvm_book);
+  }
 
-  Ok(CompileResult { core_book: hvm_book, labels, diagnostics })
+  Ok(CompileResult { core_book: hvm_book, labels, diagnostics, nets })
 }
 
 pub fn desugar_book(
@@ -98,6 +217,18 @@ pub fn desugar_book(
 
   ctx.resolve_refs()?;
 
+  if opts.runs(PassKind::CheckPartialApplications) {
+    ctx.check_partial_applications();
+  }
+
+  if opts.runs(PassKind::CheckFloatEquality) {
+    ctx.check_float_equality();
+  }
+
+  if opts.runs(PassKind::CheckShadowedVars) {
+    ctx.check_shadowed_vars();
+  }
+
   ctx.desugar_match_defs()?;
 
   ctx.fix_match_terms()?;
@@ -110,6 +241,30 @@ pub fn desugar_book(
 
   ctx.book.make_var_names_unique();
 
+  if opts.runs(PassKind::Monomorphize) {
+    ctx.book.monomorphize();
+  }
+
+  if opts.runs(PassKind::SimplifyKnownMatches) {
+    ctx.book.simplify_known_matches();
+  }
+
+  if opts.runs(PassKind::PropagateConstants) {
+    ctx.book.propagate_constants();
+  }
+
+  if opts.runs(PassKind::CombineNumOps) {
+    ctx.book.combine_num_ops();
+  }
+
+  if opts.runs(PassKind::FloatLets) {
+    ctx.book.float_lets();
+  }
+
+  if opts.runs(PassKind::MergeMatchArms) {
+    ctx.book.merge_match_arms();
+  }
+
   // Auto match linearization
   match opts.linearize_matches {
     OptLevel::Disabled => (),
@@ -132,22 +287,142 @@ pub fn desugar_book(
   // sanity check
   ctx.check_unbound_vars()?;
 
+  if opts.runs(PassKind::CheckNeverEvaluated) {
+    ctx.check_never_evaluated();
+  }
+
   // Optimizing passes
-  if opts.float_combinators {
+  if opts.runs(PassKind::EliminateIdentities) {
+    ctx.book.eliminate_identities();
+  }
+
+  if opts.runs(PassKind::FloatCombinators) {
     ctx.book.float_combinators(MAX_NET_SIZE);
   }
 
   ctx.prune(opts.prune);
 
-  if opts.merge {
+  if opts.runs(PassKind::InlineSingleUse) {
+    ctx.book.inline_single_use(MAX_NET_SIZE);
+  }
+
+  if opts.runs(PassKind::InlineSmallDefinitions) {
+    ctx.book.inline_small_definitions(opts.inline_budget);
+  }
+
+  if opts.runs(PassKind::Merge) {
     ctx.book.merge_definitions();
   }
 
   ctx.book.make_var_names_unique();
 
+  if opts.sort_definitions {
+    ctx.book.sort_definitions();
+  }
+
   if !ctx.info.has_errors() { Ok(ctx.info) } else { Err(ctx.info) }
 }
 
+/// An optional pass of the compiler, to apply it by itself with [apply_pass] or to skip it with [CompileOpts::disabled_passes].
+///
+/// Only the optimizations, checks and net passes that can be skipped without breaking the compilation
+/// are listed here. The desugaring passes that [desugar_book] always runs, like encoding the matches,
+/// linearizing the variables or resolving the `use`s, can't be disabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PassKind {
+  /// [hvm::eta_reduce::eta_reduce].
+  Eta,
+  /// [fun::transform::monomorphize].
+  Monomorphize,
+  /// [fun::transform::simplify_known_matches].
+  SimplifyKnownMatches,
+  /// [fun::transform::propagate_constants].
+  PropagateConstants,
+  /// [fun::transform::combine_num_ops].
+  CombineNumOps,
+  /// [fun::transform::float_lets].
+  FloatLets,
+  /// [fun::transform::merge_match_arms].
+  MergeMatchArms,
+  /// [fun::transform::eliminate_identities].
+  EliminateIdentities,
+  /// [fun::transform::float_combinators], with the maximum net size.
+  FloatCombinators,
+  /// [hvmc::transform::inline].
+  Inline,
+  /// [Book::inline_small_definitions], with the default inlining budget.
+  InlineSmallDefinitions,
+  /// [Book::inline_single_use], with the maximum net size.
+  InlineSingleUse,
+  /// [fun::transform::definition_merge].
+  Merge,
+  /// [fun::check::partial_application].
+  CheckPartialApplications,
+  /// [fun::check::float_equality].
+  CheckFloatEquality,
+  /// [fun::check::shadowed_vars].
+  CheckShadowedVars,
+  /// [fun::check::never_evaluated].
+  CheckNeverEvaluated,
+  /// [hvm::mutual_recursion::check_cycles].
+  CheckCycles,
+  /// [hvm::add_recursive_priority].
+  RecursivePriority,
+}
+
+/// Applies a single optimization pass to a book already desugared by [desugar_book],
+/// returning the transformed book and how many definitions the pass added, removed or changed.
+///
+/// To see the effect of a pass by itself, desugar the book with [CompileOpts::set_no_all] first.
+/// The checks and the passes over the nets leave the book unchanged,
+/// and the passes over matches find none left, since the desugaring already encoded them.
+pub fn apply_pass(mut book: Book, pass: PassKind) -> (Book, usize) {
+  let before = book.defs.clone();
+  match pass {
+    PassKind::FloatCombinators => book.float_combinators(MAX_NET_SIZE),
+    PassKind::InlineSmallDefinitions => book.inline_small_definitions(CompileOpts::default().inline_budget),
+    PassKind::InlineSingleUse => _ = book.inline_single_use(MAX_NET_SIZE),
+    PassKind::Merge => book.merge_definitions(),
+    PassKind::Monomorphize => _ = book.monomorphize(),
+    PassKind::SimplifyKnownMatches => _ = book.simplify_known_matches(),
+    PassKind::PropagateConstants => _ = book.propagate_constants(),
+    PassKind::CombineNumOps => _ = book.combine_num_ops(),
+    PassKind::FloatLets => _ = book.float_lets(),
+    PassKind::MergeMatchArms => _ = book.merge_match_arms(),
+    PassKind::EliminateIdentities => _ = book.eliminate_identities(),
+    PassKind::Eta
+    | PassKind::Inline
+    | PassKind::CheckPartialApplications
+    | PassKind::CheckFloatEquality
+    | PassKind::CheckShadowedVars
+    | PassKind::CheckNeverEvaluated
+    | PassKind::CheckCycles
+    | PassKind::RecursivePriority => (),
+  }
+  book.make_var_names_unique();
+
+  let changed =
+    before.iter().filter(|(name, def)| book.defs.get(*name).map(|new| &new.rules) != Some(&def.rules));
+  let added = book.defs.keys().filter(|name| !before.contains_key(*name));
+  let count = changed.count() + added.count();
+  (book, count)
+}
+
+/// Runs the lambda-level passes and renders the resulting book back as Bend source,
+/// showing what the syntax sugar of the program was expanded into.
+pub fn desugar_book_to_string(
+  book: &mut Book,
+  opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+) -> Result<(String, Diagnostics), Diagnostics> {
+  let diagnostics = desugar_book(book, opts, diagnostics_cfg, None)?;
+  Ok((book.display_pretty().to_string(), diagnostics))
+}
+
+/// Compiles and runs a book with the given `hvm` subcommand, reading back the result.
+///
+/// The compilation warnings are written to `warnings` before running, usually [std::io::stderr].
+#[allow(clippy::too_many_arguments)]
 pub fn run_book_with_fn(
   mut book: Book,
   run_opts: RunOpts,
@@ -156,21 +431,47 @@ pub fn run_book_with_fn(
   args: Option<Vec<Term>>,
   cmd: &str,
   arg_io: bool,
-) -> Result<Option<(Term, String, Diagnostics)>, Diagnostics> {
-  let CompileResult { core_book, labels, diagnostics } =
-    compile_book(&mut book, compile_opts.clone(), diagnostics_cfg, args)?;
+  warnings: &mut dyn std::io::Write,
+) -> Result<Option<(Term, RunInfo, Diagnostics)>, Diagnostics> {
+  let CompileResult { core_book, labels, diagnostics, .. } =
+    compile_book(&mut book, compile_opts.clone(), diagnostics_cfg, args, CompileHooks::default())?;
 
   // TODO: Printing should be taken care by the cli module, but we'd
   // like to print any warnings before running so that the user can
   // cancel the run if a problem is detected.
-  eprint!("{diagnostics}");
+  write_warnings(warnings, &diagnostics, run_opts.warn_summary)?;
+
+  let Some((net, info)) = run_hvm(&core_book, cmd, arg_io)? else {
+    return Ok(None);
+  };
+
+  let (term, diags) = readback_hvm_net(
+    &net,
+    &book,
+    &labels,
+    run_opts.linear_readback,
+    run_opts.max_readback_depth,
+    &run_opts.resugar,
+    &run_opts.resugarers,
+  );
+  Ok(Some((term, info, diags)))
+}
 
+/// Runs a compiled book with the `hvm` binary, using the given subcommand (`run`, `run-c`, `run-cu`).
+///
+/// This is the lower level part of [run_book], for when the compilation or the
+/// readback of the result need to be done differently.
+///
+/// With `io`, the output of the program goes directly to stdout and `None` is returned.
+/// Otherwise, returns the resulting net, along with the run statistics and
+/// anything the program printed before its result.
+pub fn run_hvm(core_book: &hvmc::ast::Book, cmd: &str, io: bool) -> Result<Option<(Net, RunInfo)>, String> {
   let out_path = ".out.hvm";
   std::fs::write(out_path, core_book.to_string()).map_err(|x| x.to_string())?;
   let run_fn = |out_path: &str| {
     let mut process = std::process::Command::new("hvm");
     process.arg(cmd).arg(out_path);
-    if arg_io {
+    if io {
       process.arg("--io");
       process.stdout(std::process::Stdio::inherit());
       process.spawn()?.wait_with_output()
@@ -184,22 +485,22 @@ pub fn run_book_with_fn(
   let err = String::from_utf8_lossy(&stderr);
   let status = if !status.success() { status.to_string() } else { String::new() };
 
-  if arg_io {
+  if io {
     return Ok(None);
   }
 
-  let Some((_, result)) = out.split_once("Result: ") else {
-    return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out).into());
+  let Some((effects, result)) = out.split_once("Result: ") else {
+    return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out));
   };
   let Some((result, stats)) = result.split_once('\n') else {
-    return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out).into());
+    return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out));
   };
   let Ok(net) = hvmc::ast::Net::from_str(result) else {
-    return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out).into());
+    return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out));
   };
 
-  let (term, diags) = readback_hvm_net(&net, &book, &labels, run_opts.linear_readback);
-  Ok(Some((term, stats.to_string(), diags)))
+  let effects = effects.lines().map(str::to_string).collect();
+  Ok(Some((net, RunInfo { stats: stats.to_string(), effects })))
 }
 
 pub fn run_book(
@@ -208,24 +509,199 @@ pub fn run_book(
   compile_opts: CompileOpts,
   diagnostics_cfg: DiagnosticsConfig,
   args: Option<Vec<Term>>,
-) -> Result<(Term, String, Diagnostics), Diagnostics> {
-  run_book_with_fn(book, run_opts, compile_opts, diagnostics_cfg, args, "run", false).map(Option::unwrap)
+) -> Result<(Term, RunInfo, Diagnostics), Diagnostics> {
+  let warnings = &mut std::io::stderr();
+  run_book_with_fn(book, run_opts, compile_opts, diagnostics_cfg, args, "run", false, warnings)
+    .map(Option::unwrap)
+}
+
+/// Evaluates a standalone expression against an already compiled book, as in an interactive session.
+///
+/// `book` must be the desugared book left by the [compile_book] call that returned `compiled`.
+/// See [compile_expr] for how the expression is compiled.
+pub fn eval_expr(
+  expr_src: &str,
+  book: &Book,
+  compiled: &CompileResult,
+  compile_opts: CompileOpts,
+  run_opts: RunOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  warnings: &mut dyn std::io::Write,
+) -> Result<(Term, RunInfo, Diagnostics), Diagnostics> {
+  let CompileResult { core_book, labels, diagnostics, .. } =
+    compile_expr(expr_src, book, compiled, compile_opts, diagnostics_cfg)?;
+
+  // Like in [run_book_with_fn], the warnings are printed before running.
+  write_warnings(warnings, &diagnostics, run_opts.warn_summary)?;
+
+  let (net, info) = run_hvm(&core_book, "run", false)?.unwrap();
+
+  let (term, diags) = readback_hvm_net(
+    &net,
+    book,
+    &labels,
+    run_opts.linear_readback,
+    run_opts.max_readback_depth,
+    &run_opts.resugar,
+    &run_opts.resugarers,
+  );
+  Ok((term, info, diags))
 }
 
-pub fn readback_hvm_net(net: &Net, book: &Book, labels: &Labels, linear: bool) -> (Term, Diagnostics) {
+fn write_warnings(
+  warnings: &mut dyn std::io::Write,
+  diagnostics: &Diagnostics,
+  summarized: bool,
+) -> Result<(), String> {
+  let res = if summarized {
+    write!(warnings, "{}", diagnostics.display_summarized())
+  } else {
+    write!(warnings, "{diagnostics}")
+  };
+  res.map_err(|e| format!("While writing the warnings: {e}"))
+}
+
+/// Compiles a standalone expression against an already compiled book,
+/// returning a copy of the compiled book with the expression as its entrypoint.
+///
+/// Only the expression goes through the compiler, the definitions it references are
+/// taken from `compiled` as they are, so they aren't compiled again for each expression.
+/// The entrypoint of the book can't be referenced, since the expression takes its place.
+///
+/// `book` must be the desugared book left by the [compile_book] call that returned `compiled`.
+pub fn compile_expr(
+  expr_src: &str,
+  book: &Book,
+  compiled: &CompileResult,
+  compile_opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+) -> Result<CompileResult, Diagnostics> {
+  let expr = TermParser::new(expr_src).parse_term()?;
+
+  // The already compiled definitions are replaced by stubs, so that the expression can refer to them.
+  // The constructors are generated again from the ADTs, but only the missing ones are kept.
+  let entrypoint = book.hvmc_entrypoint();
+  let stubs = compiled
+    .core_book
+    .keys()
+    .filter(|name| *name != entrypoint && !book.ctrs.contains_key(&Name::new(name.as_str())))
+    .cloned()
+    .collect::<HashSet<_>>();
+
+  let eval_name = Name::new(EVAL_ENTRY_POINT);
+  let mut eval_book = Book { adts: book.adts.clone(), ctrs: book.ctrs.clone(), ..Default::default() };
+  for name in &stubs {
+    let name = Name::new(name.as_str());
+    let rules = vec![Rule { pats: vec![], body: Term::Era, span: None }];
+    eval_book.defs.insert(name.clone(), Definition { name, rules, builtin: true });
+  }
+  let rules = vec![Rule { pats: vec![], body: expr, span: None }];
+  eval_book.defs.insert(eval_name.clone(), Definition { name: eval_name.clone(), rules, builtin: false });
+  eval_book.entrypoint = Some(eval_name);
+
+  // Inlining or merging would replace the references to the stubs by their bodies.
+  let compile_opts =
+    CompileOpts { inline: false, inline_small_definitions: false, merge: false, ..compile_opts };
+  let res = compile_book(&mut eval_book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
+
+  let mut core_book = compiled.core_book.clone();
+  for (name, net) in res.core_book.nets {
+    if name == EVAL_ENTRY_POINT {
+      core_book.insert(entrypoint.to_string(), net);
+    } else if !stubs.contains(&name) {
+      core_book.entry(name).or_insert(net);
+    }
+  }
+
+  Ok(CompileResult { diagnostics: res.diagnostics, core_book, labels: compiled.labels.clone(), nets: None })
+}
+
+pub fn readback_hvm_net(
+  net: &Net,
+  book: &Book,
+  labels: &Labels,
+  linear: bool,
+  max_depth: usize,
+  resugar: &BTreeSet<BuiltinSugar>,
+  resugarers: &[Resugarer],
+) -> (Term, Diagnostics) {
   let mut diags = Diagnostics::default();
-  let net = hvmc_to_net(net);
-  let mut term = net_to_term(&net, book, labels, linear, &mut diags);
+  let net = hvmc_to_net(net, &mut diags);
+  let mut term = net_to_term(&net, book, labels, linear, max_depth, &mut diags);
   term.expand_generated(book);
-  term.resugar_strings();
-  term.resugar_lists();
+  term.resugar_builtins(resugar);
+  term.resugar_custom(resugarers);
   (term, diags)
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+/// Information about a finished run, besides its result.
+#[derive(Clone, Debug, Default)]
+pub struct RunInfo {
+  /// The runtime stats reported by hvm.
+  pub stats: String,
+  /// The lines the program printed before its result, in order.
+  pub effects: Vec<String>,
+}
+
+impl RunInfo {
+  /// The stats reported by hvm that are written as CSV columns, in order.
+  const CSV_STATS: [&'static str; 3] = ["ITRS", "TIME", "MIPS"];
+
+  /// The header line for the rows written by [RunInfo::to_csv_row].
+  pub fn csv_header() -> String {
+    "interactions,time_secs,mips".to_string()
+  }
+
+  /// The runtime stats of this run as a line of CSV, without a trailing newline.
+  ///
+  /// Stats that hvm didn't report are left empty.
+  pub fn to_csv_row(&self) -> String {
+    let stats = self.stats_fields();
+    Self::CSV_STATS
+      .iter()
+      .map(|key| stats.iter().find(|(k, _)| k == key).map_or("", |(_, val)| val.trim_end_matches('s')))
+      .collect::<Vec<_>>()
+      .join(",")
+  }
+
+  /// The `- KEY: value` lines of the stats, as key and value pairs.
+  pub fn stats_fields(&self) -> Vec<(&str, &str)> {
+    self
+      .stats
+      .lines()
+      .filter_map(|line| line.trim().strip_prefix("- ")?.split_once(':'))
+      .map(|(key, val)| (key.trim(), val.trim()))
+      .collect()
+  }
+}
+
+#[derive(Clone, Debug)]
 pub struct RunOpts {
   pub linear_readback: bool,
   pub pretty: bool,
+  /// How many nested nodes the readback visits before giving up on the result.
+  pub max_readback_depth: usize,
+  /// The builtin types turned back into their syntax sugar on readback.
+  /// The ones left out are shown in their constructor form.
+  pub resugar: BTreeSet<BuiltinSugar>,
+  /// Custom readback rules, applied after the builtin resugaring, see [Resugarer].
+  pub resugarers: Vec<Resugarer>,
+  /// Prints only the number of warnings of each kind before running,
+  /// see [Diagnostics::display_warnings_summary].
+  pub warn_summary: bool,
+}
+
+impl Default for RunOpts {
+  fn default() -> Self {
+    Self {
+      linear_readback: false,
+      pretty: false,
+      max_readback_depth: 1 << 20,
+      resugar: BuiltinSugar::all(),
+      resugarers: vec![],
+      warn_summary: false,
+    }
+  }
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -248,7 +724,7 @@ impl OptLevel {
 
 #[derive(Clone, Debug)]
 pub struct CompileOpts {
-  /// Enables [hvmc::transform::eta_reduce].
+  /// Enables [hvm::eta_reduce::eta_reduce].
   pub eta: bool,
 
   /// Enables [fun::transform::definition_pruning] and [hvmc_net::prune].
@@ -266,8 +742,50 @@ pub struct CompileOpts {
   /// Enables [hvmc::transform::inline].
   pub inline: bool,
 
+  /// Enables inlining the small definitions, see [fun::transform::inline].
+  pub inline_small_definitions: bool,
+
+  /// The maximum size, in nodes, of the definitions inlined by [fun::transform::inline].
+  pub inline_budget: usize,
+
+  /// Enables inlining the definitions that are referenced only once, see [fun::transform::inline].
+  pub inline_single_use: bool,
+
   /// Enables [hvm::check_net_size].
   pub check_net_size: bool,
+
+  /// Enables [fun::transform::simplify_known_matches].
+  pub simplify_known_matches: bool,
+
+  /// Enables [fun::transform::float_lets].
+  pub float_lets: bool,
+
+  /// Enables [fun::transform::eliminate_identities].
+  pub eliminate_identities: bool,
+
+  /// Enables [fun::transform::merge_match_arms].
+  pub merge_match_arms: bool,
+
+  /// Enables [fun::transform::propagate_constants].
+  pub propagate_constants: bool,
+
+  /// Enables [fun::transform::monomorphize].
+  pub monomorphize: bool,
+
+  /// Enables [fun::transform::combine_num_ops].
+  pub combine_num_ops: bool,
+
+  /// Enables [fun::transform::sort_definitions], so that the order of the desugared definitions
+  /// doesn't depend on the order they were written or generated in.
+  pub sort_definitions: bool,
+
+  /// Optional passes that are skipped, even if they are enabled or would always run.
+  ///
+  /// Only covers the passes listed in [PassKind]; the desugaring passes that every program needs,
+  /// like [fun::transform::encode_match_terms] or [fun::transform::linearize_vars], always run.
+  /// Meant for finding which pass breaks a program. Disabling passes other than the optimizations
+  /// can hide errors or make the program slower, since its recursive calls are no longer delayed.
+  pub disabled_passes: BTreeSet<PassKind>,
 }
 
 impl CompileOpts {
@@ -280,8 +798,20 @@ impl CompileOpts {
       float_combinators: true,
       merge: true,
       inline: true,
+      inline_small_definitions: true,
+      inline_budget: self.inline_budget,
+      inline_single_use: true,
       linearize_matches: OptLevel::Enabled,
       check_net_size: self.check_net_size,
+      simplify_known_matches: true,
+      float_lets: true,
+      eliminate_identities: true,
+      merge_match_arms: true,
+      propagate_constants: true,
+      monomorphize: true,
+      combine_num_ops: true,
+      sort_definitions: self.sort_definitions,
+      disabled_passes: self.disabled_passes,
     }
   }
 
@@ -295,12 +825,60 @@ impl CompileOpts {
       float_combinators: false,
       merge: false,
       inline: false,
+      inline_small_definitions: false,
+      inline_budget: self.inline_budget,
+      inline_single_use: false,
       check_net_size: self.check_net_size,
+      simplify_known_matches: false,
+      float_lets: false,
+      eliminate_identities: false,
+      merge_match_arms: false,
+      propagate_constants: false,
+      monomorphize: false,
+      combine_num_ops: false,
+      sort_definitions: self.sort_definitions,
+      disabled_passes: self.disabled_passes,
     }
   }
 
+  /// The default options, with the desugared definitions in a canonical order.
+  ///
+  /// Meant for comparing the output of the compiler between versions, like in its own tests.
+  /// The rest of the compilation is already deterministic: generated definitions are named
+  /// after the definition they come from and the compiled book is ordered by name.
+  #[must_use]
+  pub fn golden() -> Self {
+    Self { sort_definitions: true, ..Self::default() }
+  }
+
+  /// Whether `pass` is enabled by its option, if it has one, and hasn't been disabled with [Self::disabled_passes].
+  pub fn runs(&self, pass: PassKind) -> bool {
+    let enabled = match pass {
+      PassKind::Eta => self.eta,
+      PassKind::Monomorphize => self.monomorphize,
+      PassKind::SimplifyKnownMatches => self.simplify_known_matches,
+      PassKind::PropagateConstants => self.propagate_constants,
+      PassKind::CombineNumOps => self.combine_num_ops,
+      PassKind::FloatLets => self.float_lets,
+      PassKind::MergeMatchArms => self.merge_match_arms,
+      PassKind::EliminateIdentities => self.eliminate_identities,
+      PassKind::FloatCombinators => self.float_combinators,
+      PassKind::Inline => self.inline,
+      PassKind::InlineSmallDefinitions => self.inline_small_definitions,
+      PassKind::InlineSingleUse => self.inline_single_use,
+      PassKind::Merge => self.merge,
+      PassKind::CheckPartialApplications
+      | PassKind::CheckFloatEquality
+      | PassKind::CheckShadowedVars
+      | PassKind::CheckNeverEvaluated
+      | PassKind::CheckCycles
+      | PassKind::RecursivePriority => true,
+    };
+    enabled && !self.disabled_passes.contains(&pass)
+  }
+
   pub fn check_for_strict(&self) {
-    if !self.float_combinators {
+    if !self.runs(PassKind::FloatCombinators) {
       println!(
         "Warning: Running in strict mode without enabling the float_combinators pass can lead to some functions expanding infinitely."
       );
@@ -323,15 +901,156 @@ impl Default for CompileOpts {
       float_combinators: true,
       merge: false,
       inline: false,
+      inline_small_definitions: false,
+      inline_budget: 8,
+      inline_single_use: false,
       check_net_size: true,
+      simplify_known_matches: false,
+      float_lets: false,
+      eliminate_identities: false,
+      merge_match_arms: false,
+      propagate_constants: false,
+      monomorphize: false,
+      combine_num_ops: false,
+      sort_definitions: false,
+      disabled_passes: BTreeSet::new(),
     }
   }
 }
 
+/// The first line of the sidecar files written by [CompileResult::to_sidecar].
+const SIDECAR_HEADER: &str = "# bend labels v1";
+
+/// Optional hooks into [compile_book], for tools that need more control over the compilation than [CompileOpts] gives.
+///
+/// The default doesn't change the compilation in any way.
+#[derive(Default)]
+pub struct CompileHooks<'a> {
+  keep: Option<KeepFn<'a>>,
+  net_pass: Option<NetPassFn<'a>>,
+  progress: Option<ProgressFn<'a>>,
+  keep_nets: bool,
+}
+
+type KeepFn<'a> = Box<dyn Fn(&Name) -> bool + 'a>;
+type NetPassFn<'a> = Box<dyn FnOnce(&mut hvmc::ast::Book) + 'a>;
+type ProgressFn<'a> = Box<dyn FnMut(usize, usize) + 'a>;
+
+impl<'a> CompileHooks<'a> {
+  /// Only compiles the definitions for which `keep` returns true,
+  /// along with the entrypoint and the builtins, constructors and generated definitions they use.
+  ///
+  /// Referencing a definition that was filtered out is an error.
+  /// Pruning still applies, so with `opts.prune` only the selected definitions reachable from the entrypoint are kept.
+  pub fn with_keep(self, keep: impl Fn(&Name) -> bool + 'a) -> Self {
+    Self { keep: Some(Box::new(keep)), ..self }
+  }
+
+  /// Calls `net_pass` on the hvm-core nets of the definitions right after the book is converted to nets,
+  /// before any of the net-level passes and checks.
+  ///
+  /// This allows net-level transformations, like custom pre-reduction or relabeling, to be added from outside the compiler.
+  pub fn with_net_pass(self, net_pass: impl FnOnce(&mut hvmc::ast::Book) + 'a) -> Self {
+    Self { net_pass: Some(Box::new(net_pass)), ..self }
+  }
+
+  /// Calls `progress` as each definition is converted to a net,
+  /// with the index of the definition and the total number of definitions, to report the progress of big compilations.
+  ///
+  /// The desugaring before and the net-level passes after the conversion aren't reported.
+  pub fn with_progress(self, progress: impl FnMut(usize, usize) + 'a) -> Self {
+    Self { progress: Some(Box::new(progress)), ..self }
+  }
+
+  /// Keeps the nets of the definitions as they were right after the conversion from terms,
+  /// before any of the net-level passes, in [CompileResult::nets].
+  pub fn with_nets(self) -> Self {
+    Self { keep_nets: true, ..self }
+  }
+}
+
 pub struct CompileResult {
   pub diagnostics: Diagnostics,
   pub core_book: hvmc::ast::Book,
   pub labels: Labels,
+  /// The nets right after the conversion from terms, if requested with [CompileHooks::with_nets].
+  pub nets: Option<hvmc::ast::Book>,
+}
+
+impl CompileResult {
+  /// Encodes the compiled book and its labels in a compact binary format,
+  /// to be loaded again with [CompileResult::from_bytes].
+  ///
+  /// The diagnostics are not included.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    hvm::binary::book_to_bytes(&self.core_book, &self.labels)
+  }
+
+  /// Decodes a compiled book encoded by [CompileResult::to_bytes], with empty diagnostics.
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, Diagnostics> {
+    let (core_book, labels) = hvm::binary::bytes_to_book(bytes)?;
+    Ok(CompileResult { diagnostics: Diagnostics::default(), core_book, labels, nets: None })
+  }
+
+  /// The labels of the compiled book as a human-readable sidecar file,
+  /// to read back the result of running the book in another process without recompiling it.
+  /// Loaded again with [CompileResult::labels_from_sidecar].
+  ///
+  /// The compiled nets keep the names of the definitions they come from,
+  /// so the labels are the only mapping that readback needs besides the book.
+  pub fn to_sidecar(&self) -> String {
+    format!("{SIDECAR_HEADER}\n{}", self.labels)
+  }
+
+  /// Loads the labels from a sidecar file written by [CompileResult::to_sidecar].
+  pub fn labels_from_sidecar(text: &str) -> Result<Labels, Diagnostics> {
+    let Some(labels) = text.strip_prefix(SIDECAR_HEADER) else {
+      return Err(format!("Expected the sidecar file to start with '{SIDECAR_HEADER}'.").into());
+    };
+    Ok(labels.parse()?)
+  }
+
+  /// Compares the compiled definitions of two results,
+  /// listing the ones that were added (`+`), removed (`-`) or changed (`~`) in `other`.
+  ///
+  /// Each entry shows the node count of the definition, or the change in it.
+  /// Definitions that are the same in both books are not listed.
+  pub fn diff(&self, other: &CompileResult) -> String {
+    let mut out = String::new();
+    for (name, net) in self.core_book.iter() {
+      match other.core_book.get(name) {
+        None => writeln!(out, "- @{name} ({} nodes)", count_nodes(net)).unwrap(),
+        Some(other_net) if other_net != net => {
+          let (old, new) = (count_nodes(net), count_nodes(other_net));
+          let delta = new as isize - old as isize;
+          writeln!(out, "~ @{name} ({old} -> {new} nodes, {delta:+})").unwrap();
+        }
+        Some(_) => {}
+      }
+    }
+    for (name, net) in other.core_book.iter() {
+      if !self.core_book.contains_key(name) {
+        writeln!(out, "+ @{name} ({} nodes)", count_nodes(net)).unwrap();
+      }
+    }
+    out
+  }
+
+  /// The number of nodes that each definition of the compiled book has.
+  pub fn definition_sizes(&self) -> HashMap<Name, usize> {
+    self.core_book.iter().map(|(name, net)| (Name::new(name), count_nodes(net))).collect()
+  }
+
+  /// Lists the compiled definitions with their node counts, from the largest to the smallest.
+  pub fn display_definition_sizes(&self) -> String {
+    let mut sizes = self.definition_sizes().into_iter().collect::<Vec<_>>();
+    sizes.sort_by(|(a_name, a_size), (b_name, b_size)| b_size.cmp(a_size).then(a_name.cmp(b_name)));
+    let mut out = String::new();
+    for (name, size) in sizes {
+      writeln!(out, "@{name} ({size} nodes)").unwrap();
+    }
+    out
+  }
 }
 
 fn maybe_grow<R, F>(f: F) -> R
@@ -340,3 +1059,138 @@ where
 {
   stacker::maybe_grow(1024 * 32, 1024 * 1024, f)
 }
+
+#[test]
+fn run_stats_csv() {
+  let info = RunInfo { stats: "- ITRS: 1234\n- TIME: 0.05s\n- MIPS: 24.68\n".to_string(), effects: vec![] };
+  assert_eq!(RunInfo::csv_header(), "interactions,time_secs,mips");
+  assert_eq!(info.to_csv_row(), "1234,0.05,24.68");
+
+  let info = RunInfo { stats: "- ITRS: 7".to_string(), effects: vec![] };
+  assert_eq!(info.to_csv_row(), "7,,");
+}
+
+#[test]
+fn validate_book_errors() {
+  let validate_with = |code: &str, opts: CompileOpts| {
+    let mut book = TermParser::new(code).parse_book(fun::Book::builtins(), false).unwrap();
+    validate_book(&mut book, DiagnosticsConfig::default(), opts)
+  };
+  let validate = |code: &str| validate_with(code, CompileOpts::default());
+  assert!(validate("main = λx (x 1)").is_ok());
+  assert!(validate("main = λx (x y)").is_err());
+  assert!(validate("data T = A | B\nmain = λx match x { T/A: 0 }").is_err());
+
+  let erased = "Erased = 1\nmain = let * = Erased; 0";
+  assert!(validate(erased).unwrap().has_severity(diagnostics::Severity::Warning));
+  let opts =
+    CompileOpts { disabled_passes: [PassKind::CheckNeverEvaluated].into(), ..CompileOpts::default() };
+  assert!(!validate_with(erased, opts).unwrap().has_severity(diagnostics::Severity::Warning));
+}
+
+#[test]
+fn disabled_passes() {
+  let desugar = |opts: CompileOpts| {
+    let mut book = TermParser::new("main = (== 1.0 2.0)").parse_book(fun::Book::builtins(), false).unwrap();
+    desugar_book(&mut book, opts, DiagnosticsConfig::default(), None).unwrap()
+  };
+  assert!(desugar(CompileOpts::default()).has_severity(diagnostics::Severity::Warning));
+
+  let opts = CompileOpts { disabled_passes: [PassKind::CheckFloatEquality].into(), ..CompileOpts::default() };
+  assert!(!desugar(opts).has_severity(diagnostics::Severity::Warning));
+
+  let opts = CompileOpts { disabled_passes: [PassKind::Eta].into(), ..CompileOpts::default() };
+  assert!(!opts.runs(PassKind::Eta) && opts.runs(PassKind::FloatCombinators) && !opts.runs(PassKind::Merge));
+}
+
+#[test]
+fn labels_sidecar_round_trip() {
+  let mut labels = Labels::default();
+  labels.dup.label_to_name.insert(1, Name::new("a"));
+  labels.tup.label_to_name.insert(2, Name::new("b"));
+  let res =
+    CompileResult { diagnostics: Diagnostics::default(), core_book: Default::default(), labels, nets: None };
+
+  let sidecar = res.to_sidecar();
+  assert_eq!(sidecar, "# bend labels v1\ndup 1 a\ntup 2 b\n");
+  let loaded = CompileResult::labels_from_sidecar(&sidecar).unwrap();
+  assert_eq!(loaded.to_string(), res.labels.to_string());
+
+  assert!(CompileResult::labels_from_sidecar("dup 1 a\n").is_err());
+  assert!(CompileResult::labels_from_sidecar("# bend labels v1\nfoo 1 a\n").is_err());
+}
+
+#[test]
+fn golden_opts_order() {
+  let desugar = |code: &str| {
+    let mut book = TermParser::new(code).parse_book(fun::Book::builtins(), false).unwrap();
+    desugar_book(&mut book, CompileOpts::golden(), DiagnosticsConfig::default(), None).unwrap();
+    let out = book.display_pretty().to_string();
+    out
+  };
+  let fst = desugar("main = (foo (bar 1))\nfoo = λx (x, 2)\nbar = λx (+ x 1)");
+  let snd = desugar("bar = λx (+ x 1)\nfoo = λx (x, 2)\nmain = (foo (bar 1))");
+  assert_eq!(fst, snd);
+}
+
+#[test]
+fn custom_resugarers() {
+  use fun::{FanKind, Num, STRINGS};
+
+  // Shows the tuples of two numbers as a string with both numbers.
+  let num_pair = Resugarer::new(|term| {
+    if let Term::Fan { fan: FanKind::Tup, els, .. } = term
+      && let [Term::Num { val: Num::U24(a) }, Term::Num { val: Num::U24(b) }] = els.as_slice()
+    {
+      *term = Term::Str { val: STRINGS.get(format!("{a}:{b}")) };
+      true
+    } else {
+      false
+    }
+  });
+  // Not tried on the terms already changed by a previous resugarer.
+  let after = Resugarer::new(|term| {
+    assert!(!matches!(term, Term::Str { .. }));
+    false
+  });
+
+  let net = "(((1 2) 3) 4)".parse::<Net>().unwrap();
+  let resugar = BuiltinSugar::all();
+  let (term, _) =
+    readback_hvm_net(&net, &Book::default(), &Labels::default(), false, 100, &resugar, &[num_pair, after]);
+  assert_eq!(term.to_string(), r#"(("1:2", 3), 4)"#);
+}
+
+#[test]
+fn compile_progress() {
+  let mut book =
+    TermParser::new("foo = λx (x, 2)\nmain = (foo 1)").parse_book(Book::builtins(), false).unwrap();
+  let mut calls = vec![];
+  let opts = CompileOpts::default();
+  let hooks = CompileHooks::default().with_progress(|idx, total| calls.push((idx, total)));
+  compile_book(&mut book, opts, DiagnosticsConfig::default(), None, hooks).unwrap();
+
+  let total = book.defs.len();
+  assert_eq!(calls, (0 .. total).map(|idx| (idx, total)).collect::<Vec<_>>());
+}
+
+#[test]
+fn warning_policy_fails_compilation() {
+  use diagnostics::{DiagnosticOrigin, Severity, WarningType};
+
+  let compile = |diagnostics_cfg| {
+    let code = "Lib/unused = 1\nunused = 2\nmain = 0";
+    let mut book = TermParser::new(code).parse_book(fun::Book::builtins(), false).unwrap();
+    compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None, CompileHooks::default())
+  };
+  let deny_lib = |orig: &DiagnosticOrigin, warn, severity| match orig {
+    DiagnosticOrigin::Rule(nam) if warn == WarningType::UnusedDefinition && nam.starts_with("Lib/") => {
+      Severity::Error
+    }
+    _ => severity,
+  };
+  assert!(compile(DiagnosticsConfig::default()).is_ok());
+  assert!(
+    compile(DiagnosticsConfig { warning_policy: Some(deny_lib), ..DiagnosticsConfig::default() }).is_err()
+  );
+}
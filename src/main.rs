@@ -1,8 +1,8 @@
 use bend::{
-  check_book, compile_book, desugar_book,
+  check_book, compile_book, desugar_book, desugar_book_to_string,
   diagnostics::{Diagnostics, DiagnosticsConfig, Severity},
   fun::{Book, Name},
-  load_file_to_book, run_book_with_fn, CompileOpts, OptLevel, RunOpts,
+  load_file_to_book, run_book_with_fn, CompileHooks, CompileOpts, OptLevel, RunOpts,
 };
 use clap::{Args, CommandFactory, Parser, Subcommand};
 use std::{
@@ -21,6 +21,9 @@ struct Cli {
 
   #[arg(short = 'e', long, global = true, help = "Use other entrypoint rather than main or Main")]
   pub entrypoint: Option<String>,
+
+  #[arg(long, global = true, help = "Show only the number of warnings of each kind")]
+  pub warn_summary: bool,
 }
 
 #[derive(Subcommand, Clone, Debug)]
@@ -184,8 +187,28 @@ pub enum OptArgs {
   NoMerge,
   Inline,
   NoInline,
+  InlineSmallDefinitions,
+  NoInlineSmallDefinitions,
+  InlineSingleUse,
+  NoInlineSingleUse,
   CheckNetSize,
   NoCheckNetSize,
+  SimplifyKnownMatches,
+  NoSimplifyKnownMatches,
+  FloatLets,
+  NoFloatLets,
+  EliminateIdentities,
+  NoEliminateIdentities,
+  MergeMatchArms,
+  NoMergeMatchArms,
+  PropagateConstants,
+  NoPropagateConstants,
+  Monomorphize,
+  NoMonomorphize,
+  CombineNumOps,
+  NoCombineNumOps,
+  SortDefinitions,
+  NoSortDefinitions,
 }
 
 fn compile_opts_from_cli(args: &Vec<OptArgs>) -> CompileOpts {
@@ -206,8 +229,28 @@ fn compile_opts_from_cli(args: &Vec<OptArgs>) -> CompileOpts {
       NoMerge => opts.merge = false,
       Inline => opts.inline = true,
       NoInline => opts.inline = false,
+      InlineSmallDefinitions => opts.inline_small_definitions = true,
+      NoInlineSmallDefinitions => opts.inline_small_definitions = false,
+      InlineSingleUse => opts.inline_single_use = true,
+      NoInlineSingleUse => opts.inline_single_use = false,
       CheckNetSize => opts.check_net_size = true,
       NoCheckNetSize => opts.check_net_size = false,
+      SimplifyKnownMatches => opts.simplify_known_matches = true,
+      NoSimplifyKnownMatches => opts.simplify_known_matches = false,
+      FloatLets => opts.float_lets = true,
+      NoFloatLets => opts.float_lets = false,
+      EliminateIdentities => opts.eliminate_identities = true,
+      NoEliminateIdentities => opts.eliminate_identities = false,
+      MergeMatchArms => opts.merge_match_arms = true,
+      NoMergeMatchArms => opts.merge_match_arms = false,
+      PropagateConstants => opts.propagate_constants = true,
+      NoPropagateConstants => opts.propagate_constants = false,
+      Monomorphize => opts.monomorphize = true,
+      NoMonomorphize => opts.monomorphize = false,
+      CombineNumOps => opts.combine_num_ops = true,
+      NoCombineNumOps => opts.combine_num_ops = false,
+      SortDefinitions => opts.sort_definitions = true,
+      NoSortDefinitions => opts.sort_definitions = false,
 
       LinearizeMatches => opts.linearize_matches = OptLevel::Enabled,
       LinearizeMatchesAlt => opts.linearize_matches = OptLevel::Alt,
@@ -227,6 +270,10 @@ pub enum WarningArgs {
   UnusedDefinition,
   RepeatedBind,
   RecursionCycle,
+  NeverEvaluated,
+  PartialApplication,
+  FloatEquality,
+  ShadowedVariable,
 }
 
 fn main() -> ExitCode {
@@ -234,9 +281,10 @@ fn main() -> ExitCode {
   compile_error!("The 'cli' feature is needed for the hvm-lang cli");
 
   let cli = Cli::parse();
+  let warn_summary = cli.warn_summary;
 
   if let Err(diagnostics) = execute_cli_mode(cli) {
-    eprint!("{diagnostics}");
+    eprint!("{}", display_diagnostics(&diagnostics, warn_summary));
     return ExitCode::FAILURE;
   }
 
@@ -245,6 +293,7 @@ fn main() -> ExitCode {
 
 fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
   let arg_verbose = cli.verbose;
+  let warn_summary = cli.warn_summary;
   let entrypoint = cli.entrypoint.take();
 
   let load_book = |path: &Path| -> Result<Book, Diagnostics> {
@@ -277,7 +326,7 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
 
       let mut book = load_book(&path)?;
       let diagnostics = check_book(&mut book, diagnostics_cfg, compile_opts)?;
-      eprintln!("{}", diagnostics);
+      eprintln!("{}", display_diagnostics(&diagnostics, warn_summary));
     }
 
     Mode::GenHvm(GenArgs { comp_opts, warn_opts, path, .. }) => {
@@ -285,9 +334,9 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
       let opts = compile_opts_from_cli(&comp_opts);
 
       let mut book = load_book(&path)?;
-      let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
+      let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None, CompileHooks::default())?;
 
-      eprint!("{}", compile_res.diagnostics);
+      eprint!("{}", display_diagnostics(&compile_res.diagnostics, warn_summary));
       println!("{}", compile_res.core_book);
     }
 
@@ -300,7 +349,7 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
       let opts = compile_opts_from_cli(&comp_opts);
 
       let mut book = load_book(&path)?;
-      let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
+      let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None, CompileHooks::default())?;
 
       let out_path = ".out.hvm";
       std::fs::write(out_path, compile_res.core_book.to_string()).map_err(|x| x.to_string())?;
@@ -330,12 +379,13 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
       let opts = compile_opts_from_cli(&comp_opts);
 
       let mut book = load_book(&path)?;
-      let diagnostics = desugar_book(&mut book, opts, diagnostics_cfg, None)?;
-
-      eprint!("{diagnostics}");
       if pretty {
-        println!("{}", book.display_pretty())
+        let (source, diagnostics) = desugar_book_to_string(&mut book, opts, diagnostics_cfg)?;
+        eprint!("{}", display_diagnostics(&diagnostics, warn_summary));
+        println!("{source}");
       } else {
+        let diagnostics = desugar_book(&mut book, opts, diagnostics_cfg, None)?;
+        eprint!("{}", display_diagnostics(&diagnostics, warn_summary));
         println!("{book}");
       }
     }
@@ -356,20 +406,30 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
 
       compile_opts.check_for_strict();
 
-      let run_opts = RunOpts { linear_readback: linear, pretty };
+      let run_opts = RunOpts { linear_readback: linear, pretty, warn_summary, ..Default::default() };
 
       let book = load_book(&path)?;
-      if let Some((term, stats, diags)) =
-        run_book_with_fn(book, run_opts, compile_opts, diagnostics_cfg, arguments, run_cmd, io)?
-      {
-        eprint!("{diags}");
+      if let Some((term, info, diags)) = run_book_with_fn(
+        book,
+        run_opts,
+        compile_opts,
+        diagnostics_cfg,
+        arguments,
+        run_cmd,
+        io,
+        &mut std::io::stderr(),
+      )? {
+        eprint!("{}", display_diagnostics(&diags, warn_summary));
+        for effect in &info.effects {
+          println!("{effect}");
+        }
         if pretty {
           println!("Result:\n{}", term.display_pretty(0));
         } else {
           println!("Result: {}", term);
         }
         if print_stats {
-          println!("{stats}");
+          println!("{}", info.stats);
         }
       }
     }
@@ -377,6 +437,10 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
   Ok(())
 }
 
+fn display_diagnostics(diagnostics: &Diagnostics, warn_summary: bool) -> String {
+  if warn_summary { diagnostics.display_summarized().to_string() } else { diagnostics.to_string() }
+}
+
 fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts) -> DiagnosticsConfig {
   fn set(cfg: &mut DiagnosticsConfig, severity: Severity, cli_val: WarningArgs) {
     match cli_val {
@@ -387,6 +451,10 @@ fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts)
         cfg.unused_definition = severity;
         cfg.repeated_bind = severity;
         cfg.recursion_cycle = severity;
+        cfg.never_evaluated = severity;
+        cfg.partial_application = severity;
+        cfg.float_equality = severity;
+        cfg.shadowed_variable = severity;
       }
       WarningArgs::IrrefutableMatch => cfg.irrefutable_match = severity,
       WarningArgs::RedundantMatch => cfg.redundant_match = severity,
@@ -394,6 +462,10 @@ fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts)
       WarningArgs::UnusedDefinition => cfg.unused_definition = severity,
       WarningArgs::RepeatedBind => cfg.repeated_bind = severity,
       WarningArgs::RecursionCycle => cfg.recursion_cycle = severity,
+      WarningArgs::NeverEvaluated => cfg.never_evaluated = severity,
+      WarningArgs::PartialApplication => cfg.partial_application = severity,
+      WarningArgs::FloatEquality => cfg.float_equality = severity,
+      WarningArgs::ShadowedVariable => cfg.shadowed_variable = severity,
     }
   }
 
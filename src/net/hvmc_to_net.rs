@@ -1,13 +1,26 @@
 use super::{INet, INode, INodes, NodeId, NodeKind::*, Port, SlotId, ROOT};
 use crate::{
-  fun::Name,
+  diagnostics::Diagnostics,
+  fun::{
+    net_to_term::{report_errors, ReadbackError},
+    Name,
+  },
   net::{CtrKind, NodeKind},
 };
 use hvmc::ast::{Net, Tree};
+use std::collections::HashMap;
 
-pub fn hvmc_to_net(net: &Net) -> INet {
+/// Converts an hvmc net to an INet.
+///
+/// The net doesn't need to have been generated by this compiler.
+/// Variables that aren't used exactly twice can't be wired,
+/// so each of their uses is replaced by an eraser and a [`ReadbackError::UnpairedVar`] is reported.
+pub fn hvmc_to_net(net: &Net, diagnostics: &mut Diagnostics) -> INet {
   let inodes = hvmc_to_inodes(net);
-  inodes_to_inet(&inodes)
+  let mut errors = vec![];
+  let inet = inodes_to_inet(&inodes, &mut errors);
+  report_errors(&errors, diagnostics);
+  inet
 }
 
 fn hvmc_to_inodes(net: &Net) -> INodes {
@@ -23,7 +36,8 @@ fn hvmc_to_inodes(net: &Net) -> INodes {
 
   // Convert all the trees forming active pairs.
   for (i, (_, tree1, tree2)) in net.redexes.iter().enumerate() {
-    let tree_root = format!("a{i}");
+    // Generated names start with '%', which can't appear in the names of an hvmc net.
+    let tree_root = format!("%a{i}");
     let mut tree1 = tree_to_inodes(tree1, tree_root.clone(), net_root, &mut n_vars);
     inodes.append(&mut tree1);
     let mut tree2 = tree_to_inodes(tree2, tree_root, net_root, &mut n_vars);
@@ -33,7 +47,7 @@ fn hvmc_to_inodes(net: &Net) -> INodes {
 }
 
 fn new_var(n_vars: &mut NodeId) -> String {
-  let new_var = format!("x{n_vars}");
+  let new_var = format!("%x{n_vars}");
   *n_vars += 1;
   new_var
 }
@@ -141,10 +155,15 @@ fn tree_to_inodes(tree: &Tree, tree_root: String, net_root: &str, n_vars: &mut N
 }
 
 // Converts INodes to an INet by linking ports based on names.
-fn inodes_to_inet(inodes: &INodes) -> INet {
+fn inodes_to_inet(inodes: &INodes, errors: &mut Vec<ReadbackError>) -> INet {
+  let mut uses = HashMap::<&str, usize>::new();
+  for name in inodes.iter().flat_map(|inode| &inode.ports) {
+    *uses.entry(name).or_default() += 1;
+  }
+
   let mut inet = INet::new();
   // Maps named inode ports to numeric inet ports.
-  let mut name_map = std::collections::HashMap::new();
+  let mut name_map = HashMap::new();
 
   for inode in inodes {
     let node = inet.new_node(inode.kind.clone());
@@ -152,9 +171,13 @@ fn inodes_to_inet(inodes: &INodes) -> INet {
       let p = Port(node, j as SlotId);
       if name == "_" {
         inet.link(p, ROOT);
-      } else if let Some(&q) = name_map.get(name) {
+      } else if uses[name.as_str()] != 2 {
+        let era = inet.new_node(Era);
+        inet.link(p, Port(era, 0));
+        inet.link(Port(era, 1), Port(era, 2));
+        errors.push(ReadbackError::UnpairedVar);
+      } else if let Some(q) = name_map.remove(name) {
         inet.link(p, q);
-        name_map.remove(name);
       } else {
         name_map.insert(name.clone(), p);
       }
@@ -1,4 +1,6 @@
 pub mod hvmc_to_net;
+pub mod net_to_graph;
+pub mod net_to_instructions;
 
 use crate::fun::Name;
 pub type BendLab = u16;
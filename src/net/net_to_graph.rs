@@ -0,0 +1,173 @@
+use crate::maybe_grow;
+use hvmc::ast::{Net, Tree};
+use std::{collections::HashMap, fmt::Write};
+
+/// The nodes and wires of an hvm-core net, to render the net itself instead of its readback.
+///
+/// Node ids are the indices in `nodes`, assigned in the order the trees are written:
+/// the root first, then the net root tree and then each redex, left to right.
+/// Port 0 of each node is its main port, and the others are its auxiliary ports in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetGraph {
+  pub nodes: Vec<GraphNode>,
+  pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+  pub kind: GraphNodeKind,
+  /// How many ports the node has, including the main port.
+  pub ports: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphNodeKind {
+  /// The free port of the net, with a single port.
+  Root,
+  Era,
+  Ctr {
+    lab: u16,
+  },
+  Ref {
+    nam: String,
+  },
+  Num {
+    val: u32,
+  },
+  Op,
+  Mat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphEdge {
+  pub a: GraphPort,
+  pub b: GraphPort,
+  /// Whether this wire connects two main ports written as a redex.
+  pub redex: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GraphPort {
+  pub node: usize,
+  pub slot: usize,
+}
+
+/// Converts an hvm-core net to a graph of its nodes and wires.
+pub fn net_to_graph(net: &Net) -> NetGraph {
+  let mut builder = GraphBuilder::default();
+  let root = builder.add_node(GraphNodeKind::Root, 1);
+  let tree = builder.tree(&net.root);
+  builder.connect(End::Port(GraphPort { node: root, slot: 0 }), tree, false);
+  for (_, a, b) in &net.redexes {
+    let a = builder.tree(a);
+    let b = builder.tree(b);
+    builder.connect(a, b, true);
+  }
+  builder.graph
+}
+
+#[derive(Default)]
+struct GraphBuilder<'a> {
+  graph: NetGraph,
+  /// The other end of each variable seen only once so far.
+  vars: HashMap<&'a str, End<'a>>,
+}
+
+#[derive(Clone, Copy)]
+enum End<'a> {
+  Port(GraphPort),
+  Var(&'a str),
+}
+
+impl<'a> GraphBuilder<'a> {
+  fn add_node(&mut self, kind: GraphNodeKind, ports: usize) -> usize {
+    self.graph.nodes.push(GraphNode { kind, ports });
+    self.graph.nodes.len() - 1
+  }
+
+  /// Adds the nodes of a tree, returning the end of the wire connected to its top.
+  fn tree(&mut self, tree: &'a Tree) -> End<'a> {
+    maybe_grow(|| {
+      let kind = match tree {
+        Tree::Var { nam } => return End::Var(nam),
+        Tree::Era => GraphNodeKind::Era,
+        Tree::Ctr { lab, .. } => GraphNodeKind::Ctr { lab: *lab },
+        Tree::Ref { nam } => GraphNodeKind::Ref { nam: nam.clone() },
+        Tree::Num { val } => GraphNodeKind::Num { val: *val },
+        Tree::Op { .. } => GraphNodeKind::Op,
+        Tree::Mat { .. } => GraphNodeKind::Mat,
+      };
+      let node = self.add_node(kind, 1 + tree.children().len());
+      for (i, child) in tree.children().enumerate() {
+        let child = self.tree(child);
+        self.connect(End::Port(GraphPort { node, slot: i + 1 }), child, false);
+      }
+      End::Port(GraphPort { node, slot: 0 })
+    })
+  }
+
+  fn connect(&mut self, a: End<'a>, b: End<'a>, redex: bool) {
+    match (a, b) {
+      (End::Port(a), End::Port(b)) => self.graph.edges.push(GraphEdge { a, b, redex }),
+      (End::Var(nam), other) | (other, End::Var(nam)) => match self.vars.remove(nam) {
+        Some(partner) => self.connect(partner, other, false),
+        None => match other {
+          // Wait for the other end of whichever variable shows up last.
+          End::Var(other_nam) if !self.vars.contains_key(other_nam) => {
+            self.vars.insert(nam, End::Var(other_nam));
+          }
+          End::Var(other_nam) => {
+            let partner = self.vars.remove(other_nam).unwrap();
+            self.vars.insert(nam, partner);
+          }
+          End::Port(_) => {
+            self.vars.insert(nam, other);
+          }
+        },
+      },
+    }
+  }
+}
+
+impl GraphNodeKind {
+  /// The label of the node, using the hvm-core syntax for numbers and references.
+  pub fn label(&self) -> String {
+    match self {
+      GraphNodeKind::Root => "ROOT".to_string(),
+      GraphNodeKind::Era => "ERA".to_string(),
+      GraphNodeKind::Ctr { lab: 0 } => "CON".to_string(),
+      GraphNodeKind::Ctr { lab: 1 } => "DUP".to_string(),
+      GraphNodeKind::Ctr { lab } => format!("CTR {lab}"),
+      GraphNodeKind::Ref { nam } => format!("@{nam}"),
+      GraphNodeKind::Num { val } => Tree::Num { val: *val }.to_string(),
+      GraphNodeKind::Op => "OPR".to_string(),
+      GraphNodeKind::Mat => "MAT".to_string(),
+    }
+  }
+}
+
+impl NetGraph {
+  /// Renders the graph in the Graphviz DOT language.
+  ///
+  /// Wires are labeled with the ports they connect at each end, and redexes are drawn in bold.
+  pub fn to_dot(&self, name: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "graph {name:?} {{").unwrap();
+    for (id, node) in self.nodes.iter().enumerate() {
+      let shape = if node.kind == GraphNodeKind::Root { "plaintext" } else { "ellipse" };
+      writeln!(out, "  n{id} [label={:?}, shape={shape}];", node.kind.label()).unwrap();
+    }
+    for edge in &self.edges {
+      let GraphEdge { a, b, redex } = edge;
+      let style = if *redex { ", style=bold" } else { "" };
+      writeln!(
+        out,
+        "  n{} -- n{} [taillabel=\"{}\", headlabel=\"{}\"{style}];",
+        a.node, b.node, a.slot, b.slot
+      )
+      .unwrap();
+    }
+    out.push_str("}\n");
+    out
+  }
+}
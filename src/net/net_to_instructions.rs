@@ -0,0 +1,79 @@
+use super::{CtrKind, INet, NodeKind, Port, SlotId};
+use std::fmt;
+
+/// An instruction to build an interaction net, for backends other than hvm-core.
+///
+/// A net is built by first allocating all of its nodes and then linking their ports.
+/// Nodes are numbered from 0 in the order they're allocated, and the first one is always the root.
+/// Port 0 of a node is its main port and ports 1 and 2 are its auxiliary ports.
+///
+/// The root only has port 1, which is the free port of the net.
+/// Erasers, references and numbers only have their main port,
+/// and every other node has all three ports.
+/// Each port is linked exactly once.
+///
+/// Written with [`fmt::Display`], each instruction is a line of text:
+/// ```text
+/// node root | node era | node con | node con.<lab> | node tup | node tup.<lab> | node dup.<lab>
+/// node ref <name> | node num <value> | node opr | node mat
+/// link <node>.<port> <node>.<port>
+/// ```
+///
+/// Numbers are encoded like in hvm-core, with their type, or the operation to apply, in the tag bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+  /// Allocates the next node.
+  Node(NodeKind),
+  /// Links two ports.
+  Link(Port, Port),
+}
+
+/// Lowers a net to the instructions that build it.
+///
+/// The nodes are allocated in the same order as in the net,
+/// and the links are sorted by the first port they connect.
+pub fn net_to_instructions(net: &INet) -> Vec<Instr> {
+  let mut instrs = net.nodes.iter().map(|node| Instr::Node(node.kind.clone())).collect::<Vec<_>>();
+  for (id, node) in net.nodes.iter().enumerate() {
+    for &slot in node.kind.slots() {
+      let port = Port(id as u64, slot);
+      let other = net.enter_port(port);
+      if port <= other {
+        instrs.push(Instr::Link(port, other));
+      }
+    }
+  }
+  instrs
+}
+
+impl NodeKind {
+  /// The ports that a node of this kind has.
+  fn slots(&self) -> &'static [SlotId] {
+    match self {
+      NodeKind::Rot => &[1],
+      NodeKind::Era | NodeKind::Ref { .. } | NodeKind::Num { .. } => &[0],
+      NodeKind::Ctr(_) | NodeKind::Opr | NodeKind::Mat => &[0, 1, 2],
+    }
+  }
+}
+
+impl fmt::Display for Instr {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Instr::Node(kind) => match kind {
+        NodeKind::Rot => write!(f, "node root"),
+        NodeKind::Era => write!(f, "node era"),
+        NodeKind::Ctr(CtrKind::Con(None)) => write!(f, "node con"),
+        NodeKind::Ctr(CtrKind::Con(Some(lab))) => write!(f, "node con.{lab}"),
+        NodeKind::Ctr(CtrKind::Tup(None)) => write!(f, "node tup"),
+        NodeKind::Ctr(CtrKind::Tup(Some(lab))) => write!(f, "node tup.{lab}"),
+        NodeKind::Ctr(CtrKind::Dup(lab)) => write!(f, "node dup.{lab}"),
+        NodeKind::Ref { def_name } => write!(f, "node ref {def_name}"),
+        NodeKind::Num { val } => write!(f, "node num {val}"),
+        NodeKind::Opr => write!(f, "node opr"),
+        NodeKind::Mat => write!(f, "node mat"),
+      },
+      Instr::Link(a, b) => write!(f, "link {}.{} {}.{}", a.node(), a.slot(), b.node(), b.slot()),
+    }
+  }
+}
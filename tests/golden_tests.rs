@@ -1,9 +1,14 @@
 use bend::{
-  compile_book, desugar_book,
+  compile_book, compile_book_cached, desugar_book,
   diagnostics::{Diagnostics, DiagnosticsConfig, Severity},
-  fun::{load_book::do_parse_book, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Name},
+  fun::{
+    load_book::do_parse_book, net_to_term::net_to_term, node_histogram::display_histogram,
+    parser::DefinitionStream, term_to_net::Labels, transform::resugar_builtins::BuiltinSugar, Book, Ctx,
+    Name, Term,
+  },
+  hvm::check_net_size::MAX_NET_SIZE,
   net::hvmc_to_net::hvmc_to_net,
-  run_book, CompileOpts, RunOpts,
+  run_book, CompileHooks, CompileOpts, PassKind, RunOpts,
 };
 use insta::assert_snapshot;
 use itertools::Itertools;
@@ -97,7 +102,7 @@ fn compile_file() {
     let compile_opts = CompileOpts::default();
     let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
 
-    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
@@ -113,18 +118,82 @@ fn compile_file_o_all() {
       ..Default::default()
     };
 
-    let res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
+    let res = compile_book(&mut book, opts, diagnostics_cfg, None, CompileHooks::default())?;
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
 
+#[test]
+fn compile_diff() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let default = compile_book(
+      &mut book.clone(),
+      CompileOpts::default(),
+      diagnostics_cfg,
+      None,
+      CompileHooks::default(),
+    )?;
+    let all = compile_book(
+      &mut book,
+      CompileOpts::default().set_all(),
+      diagnostics_cfg,
+      None,
+      CompileHooks::default(),
+    )?;
+    Ok(default.diff(&all))
+  })
+}
+
+#[test]
+fn definition_sizes() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts { prune: true, ..CompileOpts::default() };
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
+    Ok(res.display_definition_sizes())
+  })
+}
+
+#[test]
+fn compile_expr() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compiled =
+      compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None, CompileHooks::default())?;
+
+    let mut exprs_path = PathBuf::from(path);
+    assert!(exprs_path.set_extension("exprs"));
+    let exprs = std::fs::read_to_string(exprs_path).expect("Read expressions");
+
+    let mut out = String::new();
+    for expr in exprs.lines() {
+      writeln!(out, "> {expr}").unwrap();
+      match bend::compile_expr(expr, &book, &compiled, CompileOpts::default(), diagnostics_cfg) {
+        Ok(res) => {
+          for (name, net) in res.core_book.iter() {
+            if compiled.core_book.get(name) != Some(net) {
+              writeln!(out, "@{name} = {net}").unwrap();
+            }
+          }
+        }
+        Err(diags) => writeln!(out, "{diags}").unwrap(),
+      }
+    }
+    Ok(out)
+  })
+}
+
 #[test]
 fn compile_file_o_no_all() {
   run_golden_test_dir(function_name!(), &|code, path| {
     let mut book = do_parse_book(code, path, Book::builtins())?;
     let compile_opts = CompileOpts::default().set_no_all();
     let diagnostics_cfg = DiagnosticsConfig::default();
-    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
     Ok(format!("{}", res.core_book))
   })
 }
@@ -192,9 +261,28 @@ fn readback_lnet() {
   run_golden_test_dir(function_name!(), &|code, _| {
     let net = hvmc::ast::Net::from_str(code)?;
     let book = Book::default();
-    let compat_net = hvmc_to_net(&net);
     let mut diags = Diagnostics::default();
-    let term = net_to_term(&compat_net, &book, &Labels::default(), false, &mut diags);
+    let compat_net = hvmc_to_net(&net, &mut diags);
+    let term = net_to_term(
+      &compat_net,
+      &book,
+      &Labels::default(),
+      false,
+      RunOpts::default().max_readback_depth,
+      &mut diags,
+    );
+    Ok(format!("{}{}", diags, term))
+  })
+}
+
+#[test]
+fn readback_depth_limit() {
+  run_golden_test_dir(function_name!(), &|code, _| {
+    let net = hvmc::ast::Net::from_str(code)?;
+    let book = Book::default();
+    let mut diags = Diagnostics::default();
+    let compat_net = hvmc_to_net(&net, &mut diags);
+    let term = net_to_term(&compat_net, &book, &Labels::default(), false, 3, &mut diags);
     Ok(format!("{}{}", diags, term))
   })
 }
@@ -228,6 +316,109 @@ fn simplify_matches() {
   })
 }
 
+#[test]
+fn node_histogram() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    desugar_book(&mut book, CompileOpts::default(), diagnostics_cfg, None)?;
+    Ok(display_histogram(&book.node_histogram()).to_string())
+  })
+}
+
+#[test]
+fn inline_single_use() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    let opts = CompileOpts { float_combinators: false, ..CompileOpts::golden() };
+    desugar_book(&mut book, opts, diagnostics_cfg, None)?;
+    let count = book.inline_single_use(MAX_NET_SIZE);
+    book.make_var_names_unique();
+    Ok(format!("Inlined {count} definitions.\n\n{book}"))
+  })
+}
+
+#[test]
+fn apply_pass() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    desugar_book(&mut book, CompileOpts::golden().set_no_all(), diagnostics_cfg, None)?;
+    let mut out = String::new();
+    for pass in [
+      PassKind::FloatCombinators,
+      PassKind::InlineSmallDefinitions,
+      PassKind::InlineSingleUse,
+      PassKind::Merge,
+      PassKind::EliminateIdentities,
+      PassKind::CombineNumOps,
+    ] {
+      let (book, count) = bend::apply_pass(book.clone(), pass);
+      writeln!(out, "{pass:?}: {count} definitions changed\n{book}\n").unwrap();
+    }
+    Ok(out)
+  })
+}
+
+#[test]
+fn net_to_graph() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts::default();
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
+    let mut out = String::new();
+    for (name, net) in &res.core_book.nets {
+      out.push_str(&bend::net::net_to_graph::net_to_graph(net).to_dot(name));
+    }
+    Ok(out)
+  })
+}
+
+#[test]
+fn net_to_instructions() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts::default();
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None, CompileHooks::default())?;
+    let mut out = String::new();
+    for (name, net) in &res.core_book.nets {
+      let net = hvmc_to_net(net, &mut Diagnostics::default());
+      writeln!(out, "{name}:").unwrap();
+      for instr in bend::net::net_to_instructions::net_to_instructions(&net) {
+        writeln!(out, "  {instr}").unwrap();
+      }
+    }
+    Ok(out)
+  })
+}
+
+#[test]
+fn reachability_path() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Error, true);
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let mut ctx = Ctx::new(&mut book, diagnostics_cfg);
+    ctx.set_entrypoint();
+    ctx.book.encode_adts();
+    ctx.book.encode_builtins();
+    ctx.resolve_refs()?;
+
+    let mut out = String::new();
+    for (name, def) in &ctx.book.defs {
+      // Only the builtins that are kept are shown.
+      match ctx.book.reachability_path(name) {
+        Some(path) => writeln!(out, "{name}: {}", path.iter().join(" -> ")).unwrap(),
+        None if !def.builtin => writeln!(out, "{name}: unreachable").unwrap(),
+        None => {}
+      }
+    }
+    Ok(out)
+  })
+}
+
 #[test]
 fn parse_file() {
   run_golden_test_dir(function_name!(), &|code, path| {
@@ -243,6 +434,35 @@ fn parse_file() {
   })
 }
 
+#[test]
+fn sort_definitions() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig { unused_definition: Severity::Allow, ..Default::default() };
+    desugar_book(&mut book, CompileOpts::default(), diagnostics_cfg, None)?;
+    book.sort_definitions();
+    Ok(book.defs.keys().join("\n"))
+  })
+}
+
+#[test]
+fn stream_definitions() {
+  run_golden_test_dir(function_name!(), &|code, _| {
+    let mut out = String::new();
+    let mut stream = DefinitionStream::new(code, Book::builtins(), false);
+    for def in &mut stream {
+      match def {
+        Ok(def) => writeln!(out, "{}", def.display_pretty()).unwrap(),
+        Err(err) => writeln!(out, "Error:\n{err}").unwrap(),
+      }
+    }
+    let book = stream.into_book();
+    let types = book.adts.iter().filter(|(_, adt)| !adt.builtin).map(|(name, _)| name).join(", ");
+    writeln!(out, "Types: {types}").unwrap();
+    Ok(out)
+  })
+}
+
 #[test]
 fn encode_pattern_match() {
   run_golden_test_dir(function_name!(), &|code, path| {
@@ -278,7 +498,7 @@ fn encode_pattern_match() {
 #[test]
 fn desugar_file() {
   run_golden_test_dir(function_name!(), &|code, path| {
-    let compile_opts = CompileOpts::default();
+    let compile_opts = CompileOpts::golden();
     let diagnostics_cfg = DiagnosticsConfig {
       unused_definition: Severity::Allow,
       ..DiagnosticsConfig::new(Severity::Error, true)
@@ -314,13 +534,77 @@ fn hangs() {
   })
 }
 
+#[test]
+fn compile_cache() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let cache_dir = std::env::temp_dir().join("bend_compile_cache_test");
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let diagnostics_cfg = DiagnosticsConfig::default();
+
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let fresh = compile_book_cached(&mut book, CompileOpts::default(), diagnostics_cfg, None, &cache_dir)?;
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 2);
+
+    // Marks the cached nets, so that only a cache hit can return them.
+    let hvm_path = std::fs::read_dir(&cache_dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .find(|path| path.extension().is_some_and(|ext| ext == "hvm"))
+      .unwrap();
+    std::fs::write(hvm_path, format!("{}\n@cache_hit = *\n", fresh.core_book)).unwrap();
+
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let cached = compile_book_cached(&mut book, CompileOpts::default(), diagnostics_cfg, None, &cache_dir)?;
+    assert!(!fresh.core_book.to_string().contains("@cache_hit"));
+    assert!(cached.core_book.to_string().contains("@cache_hit"), "the second compilation missed the cache");
+
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    Ok(format!("{}{}", fresh.diagnostics, fresh.core_book))
+  })
+}
+
+#[test]
+fn compile_subset() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Warning, true);
+    let hooks = CompileHooks::default().with_keep(|nam| nam.starts_with("Math/"));
+    let res = compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None, hooks)?;
+    Ok(format!("{}{}", res.diagnostics, res.core_book))
+  })
+}
+
+#[test]
+fn compile_net_pass() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Allow, true);
+    let hooks = CompileHooks::default().with_net_pass(|nets| nets.retain(|nam, _| !nam.starts_with("Test/")));
+    let res = compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None, hooks)?;
+    Ok(format!("{}{}", res.diagnostics, res.core_book))
+  })
+}
+
+#[test]
+fn compile_keeping_nets() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts { prune: true, ..CompileOpts::default() };
+    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Allow, true);
+    let res =
+      compile_book(&mut book, compile_opts, diagnostics_cfg, None, CompileHooks::default().with_nets())?;
+    Ok(format!("Converted nets:\n{}\nCompiled book:\n{}", res.nets.unwrap(), res.core_book))
+  })
+}
+
 #[test]
 fn compile_entrypoint() {
   run_golden_test_dir(function_name!(), &|code, path| {
     let mut book = do_parse_book(code, path, Book::builtins())?;
     book.entrypoint = Some(Name::new("foo"));
     let diagnostics_cfg = DiagnosticsConfig { ..DiagnosticsConfig::new(Severity::Error, true) };
-    let res = compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None)?;
+    let res =
+      compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None, CompileHooks::default())?;
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
@@ -367,7 +651,7 @@ fn mutual_recursion() {
       DiagnosticsConfig { recursion_cycle: Severity::Error, ..DiagnosticsConfig::new(Severity::Allow, true) };
     let mut book = do_parse_book(code, path, Book::builtins())?;
     let opts = CompileOpts { merge: true, ..CompileOpts::default() };
-    let res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
+    let res = compile_book(&mut book, opts, diagnostics_cfg, None, CompileHooks::default())?;
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
@@ -441,7 +725,24 @@ fn scott_triggers_unused() {
     let opts = CompileOpts::default();
     let diagnostics_cfg =
       DiagnosticsConfig { unused_definition: Severity::Error, ..DiagnosticsConfig::default() };
-    let res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
+    let res = compile_book(&mut book, opts, diagnostics_cfg, None, CompileHooks::default())?;
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
+
+#[test]
+fn eval_term() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig {
+      unused_definition: Severity::Allow,
+      ..DiagnosticsConfig::new(Severity::Error, true)
+    };
+    desugar_book(&mut book, CompileOpts::default(), diagnostics_cfg, None)?;
+    let main = Term::r#ref(book.entrypoint.as_ref().unwrap());
+    let mut term = bend::fun::eval::eval_term(&book, &main)?;
+    term.expand_generated(&book);
+    term.resugar_builtins(&BuiltinSugar::all());
+    Ok(term.to_string())
+  })
+}